@@ -0,0 +1,36 @@
+extern crate cse262_project;
+
+use cse262_project::{program, run, compile, execute, Value};
+use std::time::{Duration, Instant};
+
+const FIB: &str = r#"fn main() {
+  return fib(20);
+}
+fn fib(n) {
+  if n < 2 {
+    return n;
+  }
+  return fib(n - 1) + fib(n - 2);
+}"#;
+
+const ROUNDS: u32 = 5;
+
+fn time<F: Fn() -> Result<Value, &'static str>>(f: F) -> Duration {
+  let start = Instant::now();
+  for _ in 0..ROUNDS {
+    assert_eq!(f(), Ok(Value::Number(6765)));
+  }
+  start.elapsed() / ROUNDS
+}
+
+// Compares the tree walker with the bytecode VM on a recursive fib(20).
+// Run with `cargo bench`.
+fn main() {
+  let (_, ast) = program(FIB).unwrap();
+  let bytecode = compile(&ast);
+  let tree = time(|| run(&ast));
+  let vm = time(|| execute(&bytecode));
+  println!("fib(20) tree walker: {:?}", tree);
+  println!("fib(20) bytecode vm: {:?}", vm);
+  println!("speedup: {:.1}x", tree.as_secs_f64() / vm.as_secs_f64());
+}
@@ -0,0 +1,215 @@
+use crate::builtins::{builtin, BUILTINS};
+use crate::checker::{check, infer_types, Type, Types};
+use crate::parser::parse_with_syntax;
+use crate::resolver::{resolve_names, FunctionScope, NameKind, Reference};
+use crate::syntax::{Spans, SyntaxKind, SyntaxNode, Token, TokenKind};
+use std::ops::Range;
+
+// What editors need to know about a source file, worked out once when it changes. Every
+// position is a byte offset into the source.
+pub struct Analysis {
+  diagnostics: Vec<Diagnostic>,
+  symbols: Vec<Symbol>,
+  references: Vec<Reference>,
+  scopes: Vec<FunctionScope>,
+  // The variables of the top level statements, which functions can also read
+  globals: Vec<(String, Range<usize>)>,
+  types: Types,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+  pub span: Range<usize>,
+  pub severity: Severity,
+  pub message: String,
+}
+
+// A function definition, spanning from `fn` to its closing brace
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+  pub name: String,
+  pub signature: String,
+  pub span: Range<usize>,
+  pub name_span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+  Function,
+  Variable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+  pub label: String,
+  pub kind: CompletionKind,
+  pub detail: String,
+}
+
+impl Analysis {
+
+  // A file with syntax errors is analysed for the parts that do parse
+  pub fn new(source: &str) -> Analysis {
+    let mut analysis = Analysis{diagnostics: vec![], symbols: vec![], references: vec![], scopes: vec![], globals: vec![], types: Types::default()};
+    if source.trim().is_empty() {
+      return analysis;
+    }
+    let (ast, tree, errors) = parse_with_syntax(source);
+    let spans = Spans::new(&ast, &tree);
+    // Names are bound by the same resolver that runs before the program does
+    let names = resolve_names(&ast, &spans);
+    for (span, e) in names.errors {
+      analysis.diagnostics.push(Diagnostic{span, severity: Severity::Error, message: e.message});
+    }
+    analysis.references = names.references;
+    analysis.scopes = names.scopes;
+    analysis.globals = names.globals;
+    analysis.types = infer_types(&ast);
+    analysis.signatures(&tree);
+    if let Err(errors) = check(&ast, &spans) {
+      for e in errors {
+        analysis.diagnostics.push(Diagnostic{span: e.span, severity: Severity::Warning, message: e.message});
+      }
+    }
+    for e in errors {
+      analysis.diagnostics.push(Diagnostic{span: e.span, severity: Severity::Error, message: e.message});
+    }
+    analysis
+  }
+
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  pub fn symbols(&self) -> &[Symbol] {
+    &self.symbols
+  }
+
+  // Where the function or variable named at offset is defined
+  pub fn definition(&self, offset: usize) -> Option<Range<usize>> {
+    self.reference_at(offset)?.definition.clone()
+  }
+
+  // The type of the variable named at offset, or the signature of the function, and the
+  // span of the name
+  pub fn hover(&self, offset: usize) -> Option<(Range<usize>, String)> {
+    let reference = self.reference_at(offset)?;
+    let text = match reference.kind {
+      NameKind::Function => self.symbols.iter().find(|s| s.name == reference.name)?.signature.clone(),
+      NameKind::Builtin => builtin_signature(&reference.name)?,
+      NameKind::Variable => format!("{}: {}", reference.name, self.variable_type(&reference.function, &reference.name)),
+    };
+    Some((reference.span.clone(), text))
+  }
+
+  // Every name that can be used at offset: the variables defined so far in the function
+  // around it, the globals, the functions and the builtins
+  pub fn completions(&self, offset: usize) -> Vec<Completion> {
+    let mut completions = vec![];
+    let scope = self.scopes.iter().find(|s| s.span.start <= offset && offset <= s.span.end);
+    let (function, variables) = match scope {
+      Some(scope) => (scope.function.as_ref(), &scope.variables),
+      None => ("main", &self.globals),
+    };
+    for (name, _) in variables.iter().filter(|(_, span)| span.end <= offset) {
+      complete(&mut completions, name, CompletionKind::Variable, self.variable_type(function, name));
+    }
+    if scope.is_some() {
+      for (name, _) in &self.globals {
+        complete(&mut completions, name, CompletionKind::Variable, self.variable_type("main", name));
+      }
+    }
+    for symbol in &self.symbols {
+      complete(&mut completions, &symbol.name, CompletionKind::Function, symbol.signature.clone());
+    }
+    for b in BUILTINS {
+      complete(&mut completions, b.name, CompletionKind::Function, builtin_signature(b.name).unwrap_or_default());
+    }
+    completions
+  }
+
+  fn reference_at(&self, offset: usize) -> Option<&Reference> {
+    self.references.iter().find(|r| r.span.start <= offset && offset <= r.span.end)
+  }
+
+  fn variable_type(&self, function: &str, name: &str) -> String {
+    match self.types.variables.get(function).and_then(|variables| variables.get(name)) {
+      Some(t) => type_name(t),
+      None => "any".to_string(),
+    }
+  }
+
+  // A symbol for each function, with the types inferred for its parameters and result
+  fn signatures(&mut self, tree: &SyntaxNode) {
+    for node in tree.nodes().filter(|n| n.kind == SyntaxKind::FunctionDefine) {
+      let name = match name_token(node) {
+        Some(name) => name,
+        None => continue,
+      };
+      let inferred = self.types.functions.get(&name.text);
+      let params: Vec<String> = parameters(node).iter().enumerate().map(|(ix, param)| {
+        match inferred.and_then(|(arguments, _)| arguments.get(ix)) {
+          Some(t) => format!("{}: {}", param.text, type_name(t)),
+          None => param.text.clone(),
+        }
+      }).collect();
+      let returns = inferred.map(|(_, returns)| type_name(returns)).unwrap_or_else(|| "any".to_string());
+      let signature = format!("fn {}({}) -> {}", name.text, params.join(", "), returns);
+      self.symbols.push(Symbol{name: name.text.clone(), signature, span: node.span(), name_span: span(name)});
+    }
+  }
+
+}
+
+// A name is offered once, from the innermost place that defines it
+fn complete(completions: &mut Vec<Completion>, label: &str, kind: CompletionKind, detail: String) {
+  if !completions.iter().any(|c: &Completion| c.label == label) {
+    completions.push(Completion{label: label.to_string(), kind, detail});
+  }
+}
+
+fn span(token: &Token) -> Range<usize> {
+  token.offset..token.offset + token.text.len()
+}
+
+// The name a function or variable definition gives, from its first Identifier
+fn name_token(node: &SyntaxNode) -> Option<&Token> {
+  let name = node.nodes().find(|n| n.kind == SyntaxKind::Identifier)?;
+  name.tokens().into_iter().find(|t| t.kind == TokenKind::Identifier)
+}
+
+fn parameters(function: &SyntaxNode) -> Vec<&Token> {
+  function.nodes()
+    .filter(|n| n.kind == SyntaxKind::FunctionArguments)
+    .flat_map(|params| params.nodes())
+    .filter_map(name_token)
+    .collect()
+}
+
+// Types still unknown after inference can be anything
+fn type_name(t: &Type) -> String {
+  match t {
+    Type::Var(_) => "any".to_string(),
+    t => t.to_string(),
+  }
+}
+
+// Builtins are described by annotation names, shown as the types they stand for
+fn builtin_signature(name: &str) -> Option<String> {
+  let (_, b) = builtin(name)?;
+  let named = |name: &str| match name {
+    "int" => "Number",
+    "bool" => "Bool",
+    "string" => "String",
+    "nil" => "Nil",
+    _ => "any",
+  };
+  let params: Vec<&str> = b.params.iter().map(|p| named(p)).collect();
+  Some(format!("fn {}({}) -> {}", b.name, params.join(", "), named(b.returns)))
+}
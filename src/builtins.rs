@@ -0,0 +1,157 @@
+use crate::convert::FromValue;
+use crate::output::Output;
+use crate::runtime::{RuntimeError, Value};
+use std::convert::TryFrom;
+
+// A function every script can call, given the sink print writes to. Script and host
+// functions with the same name take its place. Parameter and result types are annotation
+// names, with "any" for any value, and are what the type checker assumes.
+pub(crate) struct Builtin {
+  pub(crate) name: &'static str,
+  pub(crate) params: &'static [&'static str],
+  pub(crate) returns: &'static str,
+  pub(crate) function: fn(&Output, &[Value]) -> Result<Value, RuntimeError>,
+}
+
+// Compiled programs refer to builtins by index, so new ones go at the end
+pub(crate) const BUILTINS: &[Builtin] = &[
+  // print(value) writes the value, println(value) writes it and a newline
+  Builtin{name: "print", params: &["any"], returns: "nil", function: print},
+  Builtin{name: "println", params: &["any"], returns: "nil", function: println},
+  // abs(n), min(a, b) and max(a, b)
+  Builtin{name: "abs", params: &["int"], returns: "int", function: abs},
+  Builtin{name: "min", params: &["int", "int"], returns: "int", function: min},
+  Builtin{name: "max", params: &["int", "int"], returns: "int", function: max},
+  // sqrt(n) is the integer square root, rounded down
+  Builtin{name: "sqrt", params: &["int"], returns: "int", function: sqrt},
+  // floor(a, b) and ceil(a, b) divide, rounding down or up where `/` rounds toward zero
+  Builtin{name: "floor", params: &["int", "int"], returns: "int", function: floor},
+  Builtin{name: "ceil", params: &["int", "int"], returns: "int", function: ceil},
+  // to_string(value) gives the text print would write
+  Builtin{name: "to_string", params: &["any"], returns: "string", function: to_string},
+  // parse_int(s) gives nil when s isn't a whole number
+  Builtin{name: "parse_int", params: &["string"], returns: "int", function: parse_int},
+  // type_of(value) names the value's type as an annotation would
+  Builtin{name: "type_of", params: &["any"], returns: "string", function: type_of},
+  // assert(condition) fails the script with "Assertion failed" when condition is false
+  Builtin{name: "assert", params: &["bool"], returns: "nil", function: assert},
+  // len(value) counts the characters in a string or the items in a list or map
+  Builtin{name: "len", params: &["any"], returns: "int", function: len},
+  // get(list, i) gives the item at index i, counting from 0, and get(map, key) the value
+  // stored under key. Either is nil when there isn't one.
+  Builtin{name: "get", params: &["any", "any"], returns: "any", function: get},
+];
+
+pub(crate) fn builtin(name: &str) -> Option<(usize, &'static Builtin)> {
+  BUILTINS.iter().enumerate().find(|(_, builtin)| builtin.name == name)
+}
+
+fn number(value: &Value) -> Result<i32, RuntimeError> {
+  i32::from_value(value.clone())
+}
+
+fn print(output: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  output.write(&args[0].to_string())?;
+  Ok(Value::Nil)
+}
+
+fn println(output: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  output.write(&format!("{}\n", args[0]))?;
+  Ok(Value::Nil)
+}
+
+fn abs(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  number(&args[0])?.checked_abs().map(Value::Number).ok_or("Integer overflow")
+}
+
+fn min(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  Ok(Value::Number(number(&args[0])?.min(number(&args[1])?)))
+}
+
+fn max(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  Ok(Value::Number(number(&args[0])?.max(number(&args[1])?)))
+}
+
+fn sqrt(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let n = number(&args[0])?;
+  if n < 0 {
+    return Err("Cannot take the square root of a negative number");
+  }
+  // Newton's method, which settles on the floor of the root. The first step is
+  // (n + 1) / 2, worked out so it can't overflow.
+  let mut root = n;
+  let mut next = n / 2 + n % 2;
+  while next < root {
+    root = next;
+    next = (root + n / root) / 2;
+  }
+  Ok(Value::Number(root))
+}
+
+fn floor(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let (a, b) = (number(&args[0])?, number(&args[1])?);
+  if b == 0 {
+    return Err("Division by zero");
+  }
+  let quotient = a.checked_div(b).ok_or("Integer overflow")?;
+  Ok(Value::Number(if a % b != 0 && (a < 0) != (b < 0) { quotient - 1 } else { quotient }))
+}
+
+fn ceil(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let (a, b) = (number(&args[0])?, number(&args[1])?);
+  if b == 0 {
+    return Err("Division by zero");
+  }
+  let quotient = a.checked_div(b).ok_or("Integer overflow")?;
+  Ok(Value::Number(if a % b != 0 && (a < 0) == (b < 0) { quotient + 1 } else { quotient }))
+}
+
+fn to_string(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  Ok(Value::String(args[0].to_string()))
+}
+
+fn parse_int(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let text = String::from_value(args[0].clone())?;
+  Ok(text.trim().parse().map(Value::Number).unwrap_or(Value::Nil))
+}
+
+fn type_of(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let name = match args[0] {
+    Value::Number(_) => "int",
+    Value::Bool(_) => "bool",
+    Value::String(_) => "string",
+    Value::Nil => "nil",
+    Value::List(_) => "list",
+    Value::Map(_) => "map",
+  };
+  Ok(Value::String(name.to_string()))
+}
+
+fn assert(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  match args[0] {
+    Value::Bool(true) => Ok(Value::Nil),
+    Value::Bool(false) => Err("Assertion failed"),
+    _ => Err("Condition is not a Bool"),
+  }
+}
+
+fn len(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let len = match &args[0] {
+    Value::String(text) => text.chars().count(),
+    Value::List(list) => list.len(),
+    Value::Map(map) => map.len(),
+    _ => return Err("Expected a String, List or Map"),
+  };
+  i32::try_from(len).map(Value::Number).map_err(|_| "Integer overflow")
+}
+
+fn get(_: &Output, args: &[Value]) -> Result<Value, RuntimeError> {
+  let item = match (&args[0], &args[1]) {
+    (Value::List(list), Value::Number(ix)) => usize::try_from(*ix).ok().and_then(|ix| list.get(ix)),
+    (Value::List(_), _) => return Err("Expected a Number"),
+    (Value::Map(map), Value::String(key)) => map.get(key),
+    (Value::Map(_), _) => return Err("Expected a String"),
+    _ => return Err("Expected a List or Map"),
+  };
+  Ok(item.cloned().unwrap_or(Value::Nil))
+}
@@ -0,0 +1,606 @@
+use crate::builtins::BUILTINS;
+use crate::runtime::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+// The types a TypeAnnotation can name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Annotation {
+  Int,
+  Bool,
+  String,
+  Nil,
+}
+
+impl Annotation {
+  pub fn from_name(name: &str) -> Option<Annotation> {
+    match name {
+      "int" => Some(Annotation::Int),
+      "bool" => Some(Annotation::Bool),
+      "string" => Some(Annotation::String),
+      "nil" => Some(Annotation::Nil),
+      _ => None,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Annotation::Int => "int",
+      Annotation::Bool => "bool",
+      Annotation::String => "string",
+      Annotation::Nil => "nil",
+    }
+  }
+}
+
+// Instructions for the stack machine. Jump targets are indices into the same function's code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+  // Push a value from the constant pool
+  Constant(usize),
+  Nil,
+  // Push or pop the local in a slot of the current frame
+  Load(usize),
+  Store(usize),
+  // Pop into the global named by a slot, used by the program's top level statements
+  StoreGlobal(usize),
+  Pop,
+  Dup,
+  Add,
+  Subtract,
+  Multiply,
+  Divide,
+  Power,
+  Equal,
+  NotEqual,
+  Less,
+  Greater,
+  LessEqual,
+  GreaterEqual,
+  Not,
+  Jump(usize),
+  // Pop a Bool and jump when it is false
+  JumpIfFalse(usize),
+  // Call a function from the function table with its arguments on the stack
+  Call(usize),
+  // Replace the current frame with a call, used for a call in return position
+  TailCall(usize),
+  // Call a builtin function by its index in the builtins table
+  CallBuiltin(usize),
+  Return,
+  // Check the value on top of the stack against an annotation
+  CheckArgument(Annotation),
+  CheckVariable(Annotation),
+  CheckReturn(Annotation),
+  // Check the value the following tail call returns against an annotation, once the
+  // chain of calls it starts returns
+  ExpectReturn(Annotation),
+  // Stop with a runtime error
+  Fail(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+  pub name: String,
+  // The slot each argument is stored in, in order
+  pub params: Vec<usize>,
+  // The name of the variable in each slot. Loading a slot that was never stored reads
+  // the global with its name instead.
+  pub slots: Vec<String>,
+  pub code: Vec<Instruction>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+  pub constants: Vec<Value>,
+  pub functions: Vec<Function>,
+  // Index of the function to start at, if the program has one
+  pub main: Option<usize>,
+  // Optional debug information, empty unless compiled by compile_with_lines
+  pub lines: Vec<LineInfo>,
+}
+
+// A debug line table entry: the source line the instructions from this one on came from,
+// up to the function's next entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineInfo {
+  pub function: usize,
+  pub instruction: usize,
+  pub line: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+  BadMagic,
+  UnsupportedVersion(u16),
+  Truncated,
+  ChecksumMismatch,
+  Corrupt(&'static str),
+}
+
+impl fmt::Display for LoadError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      LoadError::BadMagic => write!(f, "not a compiled program"),
+      LoadError::UnsupportedVersion(version) => write!(f, "unsupported format version {} (expected {})", version, VERSION),
+      LoadError::Truncated => write!(f, "file is truncated"),
+      LoadError::ChecksumMismatch => write!(f, "checksum does not match, file is corrupt"),
+      LoadError::Corrupt(reason) => write!(f, "file is corrupt: {}", reason),
+    }
+  }
+}
+
+// File layout, all integers little endian:
+//   magic "C262", version u16, flags u16, payload length u32, CRC-32 of payload u32
+//   payload: constant pool, main function index, function table with code,
+//            then the line table when flags has HAS_LINES set
+pub const MAGIC: &[u8; 4] = b"C262";
+// Version 2 added list and map constants, and version 3 ExpectReturn
+pub const VERSION: u16 = 3;
+const HAS_LINES: u16 = 1;
+const HEADER_LEN: usize = 16;
+const NO_MAIN: u32 = u32::MAX;
+// How deeply list and map constants may nest
+const MAX_NESTING: usize = 64;
+
+// The only messages Fail can carry, stored in files by index
+pub const FAILURES: &[&str] = &[
+  "Undefined function",
+  "Undefined operator",
+  "Unknown Statement",
+  "Unknown Expression",
+  "Unhandled Node",
+  "Wrong number of arguments",
+];
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+  let mut crc = 0xFFFF_FFFFu32;
+  for byte in bytes {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      let mask = (!(crc & 1)).wrapping_add(1);
+      crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+    }
+  }
+  !crc
+}
+
+struct Writer {
+  bytes: Vec<u8>,
+}
+
+impl Writer {
+  fn u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  fn u32(&mut self, value: u32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  fn usize(&mut self, value: usize) {
+    self.u32(value as u32);
+  }
+
+  fn str(&mut self, value: &str) {
+    self.usize(value.len());
+    self.bytes.extend_from_slice(value.as_bytes());
+  }
+
+  fn value(&mut self, value: &Value) {
+    match value {
+      Value::Number(value) => {
+        self.u8(0);
+        self.u32(*value as u32);
+      },
+      Value::Bool(value) => {
+        self.u8(1);
+        self.u8(*value as u8);
+      },
+      Value::String(value) => {
+        self.u8(2);
+        self.str(value);
+      },
+      Value::Nil => self.u8(3),
+      Value::List(list) => {
+        self.u8(4);
+        self.usize(list.len());
+        for item in list {
+          self.value(item);
+        }
+      },
+      // Keys are sorted so the same program always gives the same bytes
+      Value::Map(map) => {
+        self.u8(5);
+        self.usize(map.len());
+        let mut keys: Vec<&String> = map.keys().collect();
+        keys.sort();
+        for key in keys {
+          self.str(key);
+          self.value(&map[key]);
+        }
+      },
+    }
+  }
+
+  fn instruction(&mut self, instruction: &Instruction) {
+    // Opcodes follow the order of the Instruction variants
+    let (opcode, operand) = match instruction {
+      Instruction::Constant(index) => (0, Some(*index)),
+      Instruction::Nil => (1, None),
+      Instruction::Load(slot) => (2, Some(*slot)),
+      Instruction::Store(slot) => (3, Some(*slot)),
+      Instruction::Pop => (4, None),
+      Instruction::Dup => (5, None),
+      Instruction::Add => (6, None),
+      Instruction::Subtract => (7, None),
+      Instruction::Multiply => (8, None),
+      Instruction::Divide => (9, None),
+      Instruction::Power => (10, None),
+      Instruction::Equal => (11, None),
+      Instruction::NotEqual => (12, None),
+      Instruction::Less => (13, None),
+      Instruction::Greater => (14, None),
+      Instruction::LessEqual => (15, None),
+      Instruction::GreaterEqual => (16, None),
+      Instruction::Not => (17, None),
+      Instruction::Jump(target) => (18, Some(*target)),
+      Instruction::JumpIfFalse(target) => (19, Some(*target)),
+      Instruction::Call(index) => (20, Some(*index)),
+      Instruction::Return => (21, None),
+      Instruction::CheckArgument(annotation) => (22, Some(*annotation as usize)),
+      Instruction::CheckVariable(annotation) => (23, Some(*annotation as usize)),
+      Instruction::CheckReturn(annotation) => (24, Some(*annotation as usize)),
+      Instruction::Fail(message) => {
+        let index = FAILURES.iter().position(|m| m == message).expect("Fail message is not in FAILURES");
+        (25, Some(index))
+      },
+      Instruction::TailCall(index) => (26, Some(*index)),
+      Instruction::StoreGlobal(slot) => (27, Some(*slot)),
+      Instruction::CallBuiltin(index) => (28, Some(*index)),
+      Instruction::ExpectReturn(annotation) => (29, Some(*annotation as usize)),
+    };
+    self.u8(opcode);
+    if let Some(operand) = operand {
+      self.usize(operand);
+    }
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  at: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+    if self.bytes.len() - self.at < len {
+      return Err(LoadError::Truncated);
+    }
+    let taken = &self.bytes[self.at..self.at + len];
+    self.at += len;
+    Ok(taken)
+  }
+
+  fn u8(&mut self) -> Result<u8, LoadError> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, LoadError> {
+    let bytes = self.take(2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn u32(&mut self) -> Result<u32, LoadError> {
+    let bytes = self.take(4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  fn usize(&mut self) -> Result<usize, LoadError> {
+    Ok(self.u32()? as usize)
+  }
+
+  // A count of items that each take at least min bytes, checked against what is left
+  fn count(&mut self, min: usize) -> Result<usize, LoadError> {
+    let count = self.usize()?;
+    if count.saturating_mul(min) > self.bytes.len() - self.at {
+      return Err(LoadError::Truncated);
+    }
+    Ok(count)
+  }
+
+  fn str(&mut self) -> Result<String, LoadError> {
+    let len = self.usize()?;
+    let bytes = self.take(len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| LoadError::Corrupt("string is not UTF-8"))
+  }
+
+  // Lists and maps nest, so depth stops a crafted file from recursing without limit
+  fn value(&mut self, depth: usize) -> Result<Value, LoadError> {
+    if depth > MAX_NESTING {
+      return Err(LoadError::Corrupt("constant is nested too deeply"));
+    }
+    Ok(match self.u8()? {
+      0 => Value::Number(self.u32()? as i32),
+      1 => Value::Bool(self.u8()? != 0),
+      2 => Value::String(self.str()?),
+      3 => Value::Nil,
+      4 => {
+        let mut list = vec![];
+        for _ in 0..self.count(1)? {
+          list.push(self.value(depth + 1)?);
+        }
+        Value::List(list)
+      },
+      5 => {
+        let mut map = HashMap::new();
+        for _ in 0..self.count(5)? {
+          let key = self.str()?;
+          map.insert(key, self.value(depth + 1)?);
+        }
+        Value::Map(map)
+      },
+      _ => return Err(LoadError::Corrupt("unknown constant type")),
+    })
+  }
+
+  fn annotation(&mut self) -> Result<Annotation, LoadError> {
+    match self.usize()? {
+      0 => Ok(Annotation::Int),
+      1 => Ok(Annotation::Bool),
+      2 => Ok(Annotation::String),
+      3 => Ok(Annotation::Nil),
+      _ => Err(LoadError::Corrupt("unknown annotation")),
+    }
+  }
+
+  fn instruction(&mut self) -> Result<Instruction, LoadError> {
+    Ok(match self.u8()? {
+      0 => Instruction::Constant(self.usize()?),
+      1 => Instruction::Nil,
+      2 => Instruction::Load(self.usize()?),
+      3 => Instruction::Store(self.usize()?),
+      4 => Instruction::Pop,
+      5 => Instruction::Dup,
+      6 => Instruction::Add,
+      7 => Instruction::Subtract,
+      8 => Instruction::Multiply,
+      9 => Instruction::Divide,
+      10 => Instruction::Power,
+      11 => Instruction::Equal,
+      12 => Instruction::NotEqual,
+      13 => Instruction::Less,
+      14 => Instruction::Greater,
+      15 => Instruction::LessEqual,
+      16 => Instruction::GreaterEqual,
+      17 => Instruction::Not,
+      18 => Instruction::Jump(self.usize()?),
+      19 => Instruction::JumpIfFalse(self.usize()?),
+      20 => Instruction::Call(self.usize()?),
+      21 => Instruction::Return,
+      22 => Instruction::CheckArgument(self.annotation()?),
+      23 => Instruction::CheckVariable(self.annotation()?),
+      24 => Instruction::CheckReturn(self.annotation()?),
+      25 => match FAILURES.get(self.usize()?) {
+        Some(message) => Instruction::Fail(message),
+        None => return Err(LoadError::Corrupt("unknown failure message")),
+      },
+      26 => Instruction::TailCall(self.usize()?),
+      27 => Instruction::StoreGlobal(self.usize()?),
+      28 => Instruction::CallBuiltin(self.usize()?),
+      29 => Instruction::ExpectReturn(self.annotation()?),
+      _ => return Err(LoadError::Corrupt("unknown opcode")),
+    })
+  }
+}
+
+impl Bytecode {
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut payload = Writer{bytes: vec![]};
+    payload.usize(self.constants.len());
+    for constant in &self.constants {
+      payload.value(constant);
+    }
+    payload.u32(self.main.map_or(NO_MAIN, |main| main as u32));
+    payload.usize(self.functions.len());
+    for function in &self.functions {
+      payload.str(&function.name);
+      payload.usize(function.params.len());
+      for param in &function.params {
+        payload.usize(*param);
+      }
+      payload.usize(function.slots.len());
+      for slot in &function.slots {
+        payload.str(slot);
+      }
+      payload.usize(function.code.len());
+      for instruction in &function.code {
+        payload.instruction(instruction);
+      }
+    }
+    let flags = if self.lines.is_empty() { 0 } else { HAS_LINES };
+    if !self.lines.is_empty() {
+      payload.usize(self.lines.len());
+      for entry in &self.lines {
+        payload.usize(entry.function);
+        payload.usize(entry.instruction);
+        payload.u32(entry.line);
+      }
+    }
+    let mut file = Writer{bytes: MAGIC.to_vec()};
+    file.bytes.extend_from_slice(&VERSION.to_le_bytes());
+    file.bytes.extend_from_slice(&flags.to_le_bytes());
+    file.usize(payload.bytes.len());
+    file.u32(crc32(&payload.bytes));
+    file.bytes.extend(payload.bytes);
+    file.bytes
+  }
+
+  // Loads a compiled program, rejecting anything truncated, corrupt or with references
+  // the virtual machine could not follow
+  pub fn from_bytes(bytes: &[u8]) -> Result<Bytecode, LoadError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+      return Err(LoadError::BadMagic);
+    }
+    let mut header = Reader{bytes, at: MAGIC.len()};
+    let version = header.u16()?;
+    if version != VERSION {
+      return Err(LoadError::UnsupportedVersion(version));
+    }
+    let flags = header.u16()?;
+    let len = header.usize()?;
+    let checksum = header.u32()?;
+    if bytes.len() < HEADER_LEN + len {
+      return Err(LoadError::Truncated);
+    }
+    if bytes.len() > HEADER_LEN + len {
+      return Err(LoadError::Corrupt("unexpected data after the program"));
+    }
+    let payload = &bytes[HEADER_LEN..];
+    if crc32(payload) != checksum {
+      return Err(LoadError::ChecksumMismatch);
+    }
+    let mut reader = Reader{bytes: payload, at: 0};
+    let mut constants = vec![];
+    for _ in 0..reader.count(1)? {
+      constants.push(reader.value(0)?);
+    }
+    let main = match reader.u32()? {
+      NO_MAIN => None,
+      main => Some(main as usize),
+    };
+    let mut functions = vec![];
+    for _ in 0..reader.count(16)? {
+      let name = reader.str()?;
+      let mut params = vec![];
+      for _ in 0..reader.count(4)? {
+        params.push(reader.usize()?);
+      }
+      let mut slots = vec![];
+      for _ in 0..reader.count(4)? {
+        slots.push(reader.str()?);
+      }
+      let mut code = vec![];
+      for _ in 0..reader.count(1)? {
+        code.push(reader.instruction()?);
+      }
+      functions.push(Function{name, params, slots, code});
+    }
+    let mut lines = vec![];
+    if flags & HAS_LINES != 0 {
+      for _ in 0..reader.count(12)? {
+        let function = reader.usize()?;
+        let instruction = reader.usize()?;
+        let line = reader.u32()?;
+        lines.push(LineInfo{function, instruction, line});
+      }
+    }
+    if reader.at != payload.len() {
+      return Err(LoadError::Corrupt("unexpected data after the program"));
+    }
+    let bytecode = Bytecode{constants, functions, main, lines};
+    bytecode.validate()?;
+    Ok(bytecode)
+  }
+
+  fn validate(&self) -> Result<(), LoadError> {
+    if let Some(main) = self.main {
+      if main >= self.functions.len() {
+        return Err(LoadError::Corrupt("main is not in the function table"));
+      }
+    }
+    for function in &self.functions {
+      if function.params.iter().any(|slot| *slot >= function.slots.len()) {
+        return Err(LoadError::Corrupt("parameter slot out of range"));
+      }
+      // Every function ends in a return, so execution never runs off the end
+      if function.code.last() != Some(&Instruction::Return) {
+        return Err(LoadError::Corrupt("function does not end with a return"));
+      }
+      for instruction in &function.code {
+        let valid = match instruction {
+          Instruction::Constant(index) => *index < self.constants.len(),
+          Instruction::Load(slot) |
+          Instruction::Store(slot) |
+          Instruction::StoreGlobal(slot) => *slot < function.slots.len(),
+          Instruction::Jump(target) |
+          Instruction::JumpIfFalse(target) => *target < function.code.len(),
+          Instruction::Call(index) |
+          Instruction::TailCall(index) => *index < self.functions.len(),
+          Instruction::CallBuiltin(index) => *index < BUILTINS.len(),
+          _ => true,
+        };
+        if !valid {
+          return Err(LoadError::Corrupt("instruction refers outside the program"));
+        }
+      }
+    }
+    for function in &self.functions {
+      self.verify_stack(function)?;
+    }
+    for entry in &self.lines {
+      match self.functions.get(entry.function) {
+        Some(function) if entry.instruction < function.code.len() => (),
+        _ => return Err(LoadError::Corrupt("line table refers outside the program")),
+      }
+    }
+    Ok(())
+  }
+
+  // Follows every path through the code tracking how many values each instruction leaves
+  // on the stack, so the VM can never pop an empty stack or return with extra values
+  fn verify_stack(&self, function: &Function) -> Result<(), LoadError> {
+    let mut depths: Vec<Option<usize>> = vec![None; function.code.len()];
+    let mut pending = vec![(0, 0)];
+    while let Some((ip, depth)) = pending.pop() {
+      match depths[ip] {
+        Some(seen) if seen == depth => continue,
+        Some(_) => return Err(LoadError::Corrupt("stack depth differs between paths")),
+        None => depths[ip] = Some(depth),
+      }
+      let instruction = &function.code[ip];
+      let (pops, pushes) = match instruction {
+        Instruction::Constant(_) |
+        Instruction::Nil |
+        Instruction::Load(_) => (0, 1),
+        Instruction::Dup => (1, 2),
+        Instruction::Store(_) |
+        Instruction::StoreGlobal(_) |
+        Instruction::Pop |
+        Instruction::JumpIfFalse(_) => (1, 0),
+        Instruction::Not |
+        Instruction::CheckArgument(_) |
+        Instruction::CheckVariable(_) |
+        Instruction::CheckReturn(_) => (1, 1),
+        Instruction::Call(index) => (self.functions[*index].params.len(), 1),
+        Instruction::TailCall(index) => (self.functions[*index].params.len(), 0),
+        Instruction::CallBuiltin(index) => (BUILTINS[*index].params.len(), 1),
+        Instruction::Jump(_) |
+        Instruction::ExpectReturn(_) |
+        Instruction::Fail(_) => (0, 0),
+        Instruction::Return => (1, 0),
+        _ => (2, 1),
+      };
+      if depth < pops {
+        return Err(LoadError::Corrupt("instruction pops an empty stack"));
+      }
+      let next = depth - pops + pushes;
+      match instruction {
+        Instruction::Return if depth != 1 => return Err(LoadError::Corrupt("return leaves values on the stack")),
+        Instruction::TailCall(_) if next != 0 => return Err(LoadError::Corrupt("return leaves values on the stack")),
+        Instruction::Return |
+        Instruction::TailCall(_) |
+        Instruction::Fail(_) => (),
+        Instruction::Jump(target) => pending.push((*target, next)),
+        Instruction::JumpIfFalse(target) => {
+          pending.push((*target, next));
+          pending.push((ip + 1, next));
+        },
+        _ => pending.push((ip + 1, next)),
+      }
+    }
+    Ok(())
+  }
+
+}
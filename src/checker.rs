@@ -0,0 +1,400 @@
+use crate::builtins::builtin;
+use crate::parser::Node;
+use crate::syntax::Spans;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+// The types a value can have at runtime, plus type variables for anything not known yet
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+  Number,
+  Bool,
+  String,
+  Nil,
+  Var(usize),
+}
+
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Type::Number => write!(f, "Number"),
+      Type::Bool => write!(f, "Bool"),
+      Type::String => write!(f, "String"),
+      Type::Nil => write!(f, "Nil"),
+      Type::Var(ix) => write!(f, "?{}", ix),
+    }
+  }
+}
+
+// An error is at the expression whose type is wrong, in the function it occurs in
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeError {
+  pub function: String,
+  pub span: Range<usize>,
+  pub message: String,
+}
+
+impl fmt::Display for TypeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "in function `{}`: {}", self.function, self.message)
+  }
+}
+
+struct Signature {
+  arguments: Vec<Type>,
+  returns: Type,
+}
+
+// The types inferred for each function's parameters and result, and for the variables of
+// each function, with the top level statements as `main`. Anything that could be any
+// type is left as a variable.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Types {
+  pub functions: HashMap<String, (Vec<Type>, Type)>,
+  pub variables: HashMap<String, HashMap<String, Type>>,
+}
+
+struct Checker<'a> {
+  // Where the checked nodes are, when errors are wanted
+  spans: Option<&'a Spans<'a>>,
+  functions: HashMap<String, Signature>,
+  // Each function's variables as they were when it had been checked
+  variables: HashMap<String, HashMap<String, Type>>,
+  // Type variable bindings, indexed by variable number
+  bindings: Vec<Option<Type>>,
+  function: String,
+  errors: Vec<TypeError>,
+}
+
+impl<'a> Checker<'a> {
+
+  fn new(spans: Option<&'a Spans<'a>>) -> Checker<'a> {
+    Checker {
+      spans,
+      functions: HashMap::new(),
+      variables: HashMap::new(),
+      bindings: Vec::new(),
+      function: String::new(),
+      errors: Vec::new(),
+    }
+  }
+
+  fn fresh(&mut self) -> Type {
+    self.bindings.push(None);
+    Type::Var(self.bindings.len() - 1)
+  }
+
+  // Follows variable bindings until reaching a concrete type or an unbound variable
+  fn resolve(&self, t: &Type) -> Type {
+    match t {
+      Type::Var(ix) => match &self.bindings[*ix] {
+        Some(bound) => self.resolve(bound),
+        None => t.clone(),
+      },
+      _ => t.clone(),
+    }
+  }
+
+  // The variable whose binding is Nil at the end of t's bindings. Nil found in a value
+  // only says the value may be empty, so the variable can still take a type.
+  fn inferred_nil(&self, t: &Type) -> Option<usize> {
+    match t {
+      Type::Var(ix) => match &self.bindings[*ix] {
+        Some(Type::Nil) => Some(*ix),
+        Some(bound) => self.inferred_nil(bound),
+        None => None,
+      },
+      _ => None,
+    }
+  }
+
+  // Nil fits any type, as it does at runtime, so a variable or return may be empty without
+  // being an error. Only nil fits a nil annotation, though.
+  fn unify(&mut self, expected: &Type, found: &Type) -> Result<Type, (Type, Type)> {
+    match (self.resolve(expected), self.resolve(found)) {
+      (Type::Var(x), Type::Var(y)) if x == y => Ok(Type::Var(x)),
+      (Type::Var(x), t) | (t, Type::Var(x)) => {
+        self.bindings[x] = Some(t.clone());
+        Ok(t)
+      },
+      (t, Type::Nil) => Ok(t),
+      (Type::Nil, t) => match self.inferred_nil(expected) {
+        Some(ix) => {
+          self.bindings[ix] = Some(t.clone());
+          Ok(t)
+        },
+        None => Err((Type::Nil, t)),
+      },
+      (x, y) if x == y => Ok(x),
+      (x, y) => Err((x, y)),
+    }
+  }
+
+  fn expect(&mut self, found: &Type, expected: &Type, context: &str, at: &Node) {
+    if let Err((expected, found)) = self.unify(expected, found) {
+      self.error(format!("{} expects {} but found {}", context, expected, found), at);
+    }
+  }
+
+  fn error(&mut self, message: String, at: &Node) {
+    let span = self.spans.map_or(0..0, |spans| spans.get(at));
+    self.errors.push(TypeError{function: self.function.clone(), span, message});
+  }
+
+  // The type named by an annotation, or a fresh variable to infer when there is none
+  fn annotated(&mut self, annotation: Option<&Node>) -> Type {
+    match annotation {
+      Some(Node::TypeAnnotation{value}) => self.named(value),
+      _ => self.fresh(),
+    }
+  }
+
+  // Builtins take "any" for arguments of any type, which is a fresh variable
+  fn named(&mut self, name: &str) -> Type {
+    match name {
+      "int" => Type::Number,
+      "bool" => Type::Bool,
+      "string" => Type::String,
+      "nil" => Type::Nil,
+      _ => self.fresh(),
+    }
+  }
+
+  fn check_program(&mut self, node: &Node) {
+    let children = match node {
+      Node::Program{children} => children,
+      _ => return,
+    };
+    // Register every signature first so calls can come before the definition
+    for n in children {
+      if let Node::FunctionDefine{children} = n {
+        if let Some(Node::Identifier{value}) = children.first() {
+          let arguments = match children.get(1) {
+            Some(Node::FunctionArguments{children}) => {
+              children.iter().map(|arg| match arg {
+                Node::Expression{children} => self.annotated(children.get(1)),
+                _ => self.fresh(),
+              }).collect()
+            },
+            _ => vec![],
+          };
+          let returns = children.iter().skip(1).take(2).find(|n| matches!(n, Node::TypeAnnotation{..}));
+          let returns = self.annotated(returns);
+          self.functions.insert(value.clone(), Signature{arguments, returns});
+        }
+      }
+    }
+    // Check main last so that a bad argument is reported at the call rather than in the callee
+    let (entry, functions): (Vec<&Node>, Vec<&Node>) = children.iter()
+      .filter(|n| matches!(n, Node::FunctionDefine{..}))
+      .partition(|n| is_main(n));
+    let mut main = vec![];
+    for n in functions.into_iter().chain(entry) {
+      if let Node::FunctionDefine{children} = n {
+        self.check_function(children);
+      }
+    }
+    for n in children {
+      if let Node::Expression{..} | Node::Statement{..} = n {
+        main.push(n);
+      }
+    }
+    if !main.is_empty() {
+      self.function = "main".to_string();
+      let mut env = HashMap::new();
+      for n in main {
+        self.check_statement(n, &mut env);
+      }
+      self.variables.entry("main".to_string()).or_default().extend(env);
+    }
+  }
+
+  fn check_function(&mut self, children: &[Node]) {
+    let name = match children.first() {
+      Some(Node::Identifier{value}) => value.clone(),
+      _ => return,
+    };
+    self.function = name.clone();
+    let mut env = HashMap::new();
+    if let Some(Node::FunctionArguments{children}) = children.get(1) {
+      for (ix, arg) in children.iter().enumerate() {
+        if let Node::Expression{children} = arg {
+          if let Some(Node::Identifier{value}) | Some(Node::Local{value, ..}) = children.first() {
+            let t = match self.functions.get(&name).and_then(|s| s.arguments.get(ix)) {
+              Some(t) => t.clone(),
+              None => self.fresh(),
+            };
+            env.insert(value.clone(), t);
+          }
+        }
+      }
+    }
+    self.check_block(&children[1..], &mut env);
+    self.variables.insert(name, env);
+  }
+
+  fn check_block(&mut self, statements: &[Node], env: &mut HashMap<String, Type>) {
+    for n in statements {
+      self.check_statement(n, env);
+    }
+  }
+
+  fn check_statement(&mut self, node: &Node, env: &mut HashMap<String, Type>) {
+    match node {
+      Node::Statement{children} => self.check_statement(&children[0], env),
+      Node::FunctionReturn{children} => {
+        let t = match children.first() {
+          Some(value) => self.infer(value, env),
+          None => Type::Nil,
+        };
+        let returns = self.functions.get(&self.function).map(|s| s.returns.clone());
+        if let Some(returns) = returns {
+          self.expect(&t, &returns, "return", children.first().unwrap_or(node));
+        }
+      },
+      Node::VariableDefine{children} => {
+        let mut t = self.infer(&children[1], env);
+        if let Some(annotation) = children.get(2) {
+          let annotated = self.annotated(Some(annotation));
+          self.expect(&t, &annotated, "annotation", &children[1]);
+          t = annotated;
+        }
+        if let Node::Identifier{value} | Node::Local{value, ..} = &children[0] {
+          env.insert(value.clone(), t);
+        }
+      },
+      Node::If{condition, children} => {
+        for c in condition {
+          let t = self.infer(c, env);
+          self.expect(&t, &Type::Bool, "if condition", c);
+        }
+        for body in children {
+          if let Node::FunctionStatements{children} = body {
+            self.check_block(children, env);
+          }
+        }
+      },
+      Node::While{condition, children} => {
+        let t = self.infer(&condition[0], env);
+        self.expect(&t, &Type::Bool, "while condition", &condition[0]);
+        self.check_block(children, env);
+      },
+      Node::Expression{children} => {
+        match &children[0] {
+          Node::If{..} => self.check_statement(&children[0], env),
+          _ => {
+            self.infer(node, env);
+          },
+        }
+      },
+      _ => (),
+    }
+  }
+
+  fn infer(&mut self, node: &Node, env: &mut HashMap<String, Type>) -> Type {
+    match node {
+      Node::Expression{children} |
+      Node::Condition{conditions: children} => self.infer(&children[0], env),
+      Node::Number{..} => Type::Number,
+      Node::Bool{..} => Type::Bool,
+      Node::String{..} => Type::String,
+      Node::Nil => Type::Nil,
+      Node::Identifier{value} |
+      Node::Local{value, ..} => {
+        match env.get(value) {
+          Some(t) => t.clone(),
+          None => self.fresh(),
+        }
+      },
+      Node::MathExpression{name, children} => {
+        for child in children {
+          let t = self.infer(child, env);
+          self.expect(&t, &Type::Number, &format!("`{}`", name), child);
+        }
+        Type::Number
+      },
+      Node::TestEquality{children} => {
+        for child in children {
+          self.infer(child, env);
+        }
+        Type::Bool
+      },
+      Node::ConditionExpression{name, children} => {
+        let operand = match name.as_ref() {
+          "!" | "&&" | "&" | "||" | "|" => Some(Type::Bool),
+          "<" | ">" | "<=" | ">=" => Some(Type::Number),
+          _ => None,
+        };
+        for child in children {
+          let t = self.infer(child, env);
+          if let Some(operand) = &operand {
+            self.expect(&t, operand, &format!("`{}`", name), child);
+          }
+        }
+        Type::Bool
+      },
+      Node::FunctionCall{name, children} => {
+        let in_args = match children.first() {
+          Some(Node::FunctionArguments{children}) => children.as_slice(),
+          _ => children.as_slice(),
+        };
+        let arg_types: Vec<Type> = in_args.iter().map(|arg| self.infer(arg, env)).collect();
+        // A builtin gets fresh variables at each call, so print can take a different type each time
+        let signature = match self.functions.get(name) {
+          Some(s) => Some((s.arguments.clone(), s.returns.clone())),
+          None => builtin(name).map(|(_, b)| (b.params.iter().map(|p| self.named(p)).collect(), self.named(b.returns))),
+        };
+        match signature {
+          Some((arguments, returns)) => {
+            if arguments.len() != arg_types.len() {
+              self.error(format!("`{}` takes {} arguments but was given {}", name, arguments.len(), arg_types.len()), node);
+            }
+            for (ix, (expected, found)) in arguments.iter().zip(arg_types.iter()).enumerate() {
+              self.expect(found, expected, &format!("argument {} of `{}`", ix + 1, name), &in_args[ix]);
+            }
+            returns
+          },
+          // Unknown functions are reported at runtime, so we learn nothing about the result
+          None => self.fresh(),
+        }
+      },
+      _ => self.fresh(),
+    }
+  }
+
+}
+
+fn is_main(node: &Node) -> bool {
+  match node {
+    Node::FunctionDefine{children} => matches!(children.first(), Some(Node::Identifier{value}) if value == "main"),
+    _ => false,
+  }
+}
+
+// Infers types for every function in the program and returns all mismatches found, each
+// located with the spans of the tree. This is optional: the runtime does the same checks
+// as it goes.
+pub fn check(node: &Node, spans: &Spans) -> Result<(), Vec<TypeError>> {
+  let mut checker = Checker::new(Some(spans));
+  checker.check_program(node);
+  if checker.errors.is_empty() {
+    Ok(())
+  } else {
+    Err(checker.errors)
+  }
+}
+
+// Infers types the same way, keeping what was found instead of the mismatches
+pub fn infer_types(node: &Node) -> Types {
+  let mut checker = Checker::new(None);
+  checker.check_program(node);
+  let functions = checker.functions.iter().map(|(name, signature)| {
+    let arguments = signature.arguments.iter().map(|t| checker.resolve(t)).collect();
+    (name.clone(), (arguments, checker.resolve(&signature.returns)))
+  }).collect();
+  let variables = checker.variables.iter().map(|(function, env)| {
+    (function.clone(), env.iter().map(|(name, t)| (name.clone(), checker.resolve(t))).collect())
+  }).collect();
+  Types{functions, variables}
+}
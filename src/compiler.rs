@@ -0,0 +1,482 @@
+use crate::builtins::builtin;
+use crate::bytecode::{Annotation, Bytecode, Function, Instruction, LineInfo};
+use crate::parser::Node;
+use crate::runtime::{is_return, tail_call, Value};
+use crate::syntax::Spans;
+use std::collections::HashMap;
+use std::{iter, slice};
+
+struct Signature {
+  index: usize,
+  params: Vec<Option<Annotation>>,
+}
+
+// A function's statements. The top level statements making up main return the value of
+// the last one.
+struct Body<'n> {
+  statements: Vec<&'n Node>,
+  returns_last: bool,
+}
+
+struct Compiler<'a> {
+  constants: Vec<Value>,
+  signatures: HashMap<String, Signature>,
+  // Where the nodes are and the offset each line of the source starts at, when compiling
+  // with a line table
+  positions: Option<(&'a Spans<'a>, Vec<usize>)>,
+  lines: Vec<LineInfo>,
+  // State for the function being compiled
+  index: usize,
+  slots: HashMap<String, usize>,
+  code: Vec<Instruction>,
+  returns: Option<Annotation>,
+  // True while compiling main made from the top level statements, whose lets are globals
+  global: bool,
+}
+
+impl<'a> Compiler<'a> {
+
+  fn emit(&mut self, instruction: Instruction) -> usize {
+    self.code.push(instruction);
+    self.code.len() - 1
+  }
+
+  // Points a previously emitted jump at the next instruction
+  fn patch(&mut self, at: usize) {
+    let target = self.code.len();
+    match &mut self.code[at] {
+      Instruction::Jump(to) |
+      Instruction::JumpIfFalse(to) => *to = target,
+      _ => (),
+    }
+  }
+
+  fn constant(&mut self, value: Value) {
+    let index = match self.constants.iter().position(|c| *c == value) {
+      Some(index) => index,
+      None => {
+        self.constants.push(value);
+        self.constants.len() - 1
+      },
+    };
+    self.emit(Instruction::Constant(index));
+  }
+
+  // Variables are function scoped, so a name gets the same slot wherever it appears
+  fn slot(&mut self, name: &str) -> usize {
+    let next = self.slots.len();
+    *self.slots.entry(name.to_string()).or_insert(next)
+  }
+
+  fn emit_return(&mut self) {
+    if let Some(annotation) = self.returns {
+      self.emit(Instruction::CheckReturn(annotation));
+    }
+    self.emit(Instruction::Return);
+  }
+
+  // Starts a line table entry at the next instruction when node is on a new line
+  fn line(&mut self, node: &Node) {
+    let (spans, starts) = match &self.positions {
+      Some(positions) => positions,
+      None => return,
+    };
+    let span = spans.get(node);
+    if span.is_empty() {
+      return;
+    }
+    let line = starts.partition_point(|&start| start <= span.start) as u32;
+    let (function, instruction) = (self.index, self.code.len());
+    match self.lines.last_mut() {
+      Some(last) if last.function == function && last.instruction == instruction => last.line = line,
+      Some(last) if last.function == function && last.line == line => (),
+      _ => self.lines.push(LineInfo{function, instruction, line}),
+    }
+  }
+
+  fn function(&mut self, index: usize, name: &str, body: &Body) -> Function {
+    self.index = index;
+    self.slots = HashMap::new();
+    self.code = vec![];
+    self.returns = body.statements.iter().take(2).find_map(|n| match n {
+      Node::TypeAnnotation{value} => Annotation::from_name(value),
+      _ => None,
+    });
+    let mut params = vec![];
+    if let Some(Node::FunctionArguments{children}) = body.statements.first() {
+      for (ix, param) in children.iter().enumerate() {
+        let slot = match param {
+          Node::Expression{children} => match children.first() {
+            Some(Node::Identifier{value}) |
+            Some(Node::Local{value, ..}) => self.slot(value),
+            _ => self.slot(&format!("#{}", ix)),
+          },
+          _ => self.slot(&format!("#{}", ix)),
+        };
+        params.push(slot);
+      }
+    }
+    match body.statements.split_last() {
+      Some((last, rest)) if body.returns_last => {
+        for n in rest {
+          self.statement(n);
+        }
+        self.line(last);
+        self.returning(last);
+      },
+      _ => {
+        for n in &body.statements {
+          self.statement(n);
+        }
+      },
+    }
+    // Falling off the end returns nil
+    self.emit(Instruction::Nil);
+    self.emit_return();
+    let mut slots = vec![String::new(); self.slots.len()];
+    for (name, slot) in self.slots.drain() {
+      slots[slot] = name;
+    }
+    Function{
+      name: name.to_string(),
+      params,
+      slots,
+      code: std::mem::take(&mut self.code),
+    }
+  }
+
+  fn block(&mut self, statements: &[Node]) {
+    for n in statements {
+      self.statement(n);
+    }
+  }
+
+  fn statement(&mut self, n: &Node) {
+    match n {
+      Node::FunctionArguments{..} |
+      Node::TypeAnnotation{..} => (),
+      Node::FunctionReturn{..} |
+      Node::Statement{..} if is_return(n) => {
+        self.line(n);
+        self.returning(n);
+      },
+      Node::If{condition, children} => {
+        let mut ends = vec![];
+        for (ix, c) in condition.iter().enumerate() {
+          self.line(c);
+          self.value(c);
+          let next = self.emit(Instruction::JumpIfFalse(0));
+          if let Some(Node::FunctionStatements{children}) = children.get(ix) {
+            self.block(children);
+          }
+          ends.push(self.emit(Instruction::Jump(0)));
+          self.patch(next);
+        }
+        if let Some(Node::FunctionStatements{children}) = children.get(condition.len()) {
+          self.block(children);
+        }
+        for end in ends {
+          self.patch(end);
+        }
+      },
+      Node::While{condition, children} => {
+        let start = self.code.len();
+        self.line(&condition[0]);
+        self.value(&condition[0]);
+        let end = self.emit(Instruction::JumpIfFalse(0));
+        self.block(children);
+        self.emit(Instruction::Jump(start));
+        self.patch(end);
+      },
+      _ => {
+        self.line(n);
+        self.value(n);
+        self.emit(Instruction::Pop);
+      },
+    }
+  }
+
+  // Returns the value of a statement. A function hands its frame over to the function it
+  // returns the value of.
+  fn returning(&mut self, n: &Node) {
+    match tail_call(n) {
+      Some((name, in_args)) => self.call(name, in_args, true),
+      _ => {
+        self.value(n);
+        self.emit_return();
+      },
+    }
+  }
+
+  // Emits code that leaves the value of the node on the stack, as Runtime::run would return it
+  fn value(&mut self, node: &Node) {
+    match node {
+      Node::MathExpression{name, children} => {
+        self.value(&children[0]);
+        self.value(&children[1]);
+        self.emit(match name.as_ref() {
+          "+" => Instruction::Add,
+          "-" => Instruction::Subtract,
+          "*" => Instruction::Multiply,
+          "/" => Instruction::Divide,
+          "^" => Instruction::Power,
+          _ => Instruction::Fail("Undefined operator"),
+        });
+      },
+      Node::FunctionCall{name, children} => {
+        let in_args = match children.first() {
+          Some(Node::FunctionArguments{children}) => children.as_slice(),
+          _ => children.as_slice(),
+        };
+        self.call(name, in_args, false);
+      },
+      Node::FunctionReturn{children} => {
+        match children.first() {
+          Some(value) => self.value(value),
+          None => {
+            self.emit(Instruction::Nil);
+          },
+        }
+      },
+      Node::Identifier{value} |
+      Node::Local{value, ..} => {
+        let slot = self.slot(value);
+        self.emit(Instruction::Load(slot));
+      },
+      Node::Statement{children} => {
+        match children[0] {
+          Node::VariableDefine{..} |
+          Node::FunctionReturn{..} |
+          Node::Expression{..} => self.value(&children[0]),
+          _ => {
+            self.emit(Instruction::Fail("Unknown Statement"));
+          },
+        }
+      },
+      Node::VariableDefine{children} => {
+        self.value(&children[1]);
+        if let Some(Node::TypeAnnotation{value}) = children.get(2) {
+          if let Some(annotation) = Annotation::from_name(value) {
+            self.emit(Instruction::CheckVariable(annotation));
+          }
+        }
+        if let Node::Identifier{value} | Node::Local{value, ..} = &children[0] {
+          let slot = self.slot(value);
+          self.emit(Instruction::Dup);
+          self.emit(if self.global { Instruction::StoreGlobal(slot) } else { Instruction::Store(slot) });
+        }
+      },
+      Node::Expression{children} => {
+        match &children[0] {
+          Node::MathExpression{..} |
+          Node::Number{..} |
+          Node::FunctionCall{..} |
+          Node::String{..} |
+          Node::Bool{..} |
+          Node::Nil |
+          Node::Identifier{..} |
+          Node::Local{..} => self.value(&children[0]),
+          _ => {
+            self.emit(Instruction::Fail("Unknown Expression"));
+          },
+        }
+      },
+      Node::Number{value} => self.constant(Value::Number(*value)),
+      Node::String{value} => self.constant(Value::String(value.clone())),
+      Node::Bool{value} => self.constant(Value::Bool(*value)),
+      Node::Nil => {
+        self.emit(Instruction::Nil);
+      },
+      Node::Condition{conditions} => self.value(&conditions[0]),
+      Node::TestEquality{children} => {
+        self.value(&children[0]);
+        self.value(&children[1]);
+        self.emit(Instruction::Equal);
+      },
+      Node::ConditionExpression{name, children} => self.condition(name, children),
+      _ => {
+        self.emit(Instruction::Fail("Unhandled Node"));
+      },
+    }
+  }
+
+  fn call(&mut self, name: &str, in_args: &[Node], tail: bool) {
+    let (index, params) = match self.signatures.get(name) {
+      Some(signature) => (signature.index, signature.params.clone()),
+      None if builtin(name).is_some() => return self.call_builtin(name, in_args, tail),
+      None => {
+        self.emit(Instruction::Fail("Undefined function"));
+        return;
+      },
+    };
+    if in_args.len() != params.len() {
+      self.emit(Instruction::Fail("Wrong number of arguments"));
+      return;
+    }
+    for (arg, annotation) in in_args.iter().zip(params) {
+      self.value(arg);
+      if let Some(annotation) = annotation {
+        self.emit(Instruction::CheckArgument(annotation));
+      }
+    }
+    if tail {
+      // The function called in this one's place returns for it, so its value is checked
+      // against this one's annotation too
+      if let Some(annotation) = self.returns {
+        self.emit(Instruction::ExpectReturn(annotation));
+      }
+      self.emit(Instruction::TailCall(index));
+    } else {
+      self.emit(Instruction::Call(index));
+    }
+  }
+
+  // Builtins take exactly their arity and run without a frame, so in return position the
+  // call is followed by an ordinary return
+  fn call_builtin(&mut self, name: &str, in_args: &[Node], tail: bool) {
+    let (index, builtin) = builtin(name).expect("not a builtin");
+    if in_args.len() != builtin.params.len() {
+      self.emit(Instruction::Fail("Wrong number of arguments"));
+      return;
+    }
+    for arg in in_args {
+      self.value(arg);
+    }
+    self.emit(Instruction::CallBuiltin(index));
+    if tail {
+      self.emit_return();
+    }
+  }
+
+  fn condition(&mut self, name: &str, children: &[Node]) {
+    match name {
+      "!" => {
+        self.value(&children[0]);
+        self.emit(Instruction::Not);
+      },
+      // Both short circuit, and each operand that runs must be a Bool
+      "&&" | "&" => {
+        self.value(&children[0]);
+        let lhs_false = self.emit(Instruction::JumpIfFalse(0));
+        self.value(&children[1]);
+        let rhs_false = self.emit(Instruction::JumpIfFalse(0));
+        self.constant(Value::Bool(true));
+        let end = self.emit(Instruction::Jump(0));
+        self.patch(lhs_false);
+        self.patch(rhs_false);
+        self.constant(Value::Bool(false));
+        self.patch(end);
+      },
+      "||" | "|" => {
+        self.value(&children[0]);
+        let lhs_false = self.emit(Instruction::JumpIfFalse(0));
+        self.constant(Value::Bool(true));
+        let lhs_end = self.emit(Instruction::Jump(0));
+        self.patch(lhs_false);
+        self.value(&children[1]);
+        let rhs_false = self.emit(Instruction::JumpIfFalse(0));
+        self.constant(Value::Bool(true));
+        let rhs_end = self.emit(Instruction::Jump(0));
+        self.patch(rhs_false);
+        self.constant(Value::Bool(false));
+        self.patch(lhs_end);
+        self.patch(rhs_end);
+      },
+      _ => {
+        self.value(&children[0]);
+        self.value(&children[1]);
+        self.emit(match name {
+          "!=" => Instruction::NotEqual,
+          "<" => Instruction::Less,
+          ">" => Instruction::Greater,
+          "<=" => Instruction::LessEqual,
+          ">=" => Instruction::GreaterEqual,
+          _ => Instruction::Fail("Undefined operator"),
+        });
+      },
+    }
+  }
+
+}
+
+// Compiles a program to bytecode with the same behaviour as running it with runtime::run
+pub fn compile(node: &Node) -> Bytecode {
+  compile_program(node, None)
+}
+
+// The same, with a line table giving the line of the source each statement's code came
+// from, found with the spans of the program's syntax tree
+pub fn compile_with_lines(node: &Node, spans: &Spans, source: &str) -> Bytecode {
+  let starts = iter::once(0).chain(source.match_indices('\n').map(|(ix, _)| ix + 1)).collect();
+  compile_program(node, Some((spans, starts)))
+}
+
+fn compile_program<'a, 'n>(node: &'n Node, positions: Option<(&'a Spans<'a>, Vec<usize>)>) -> Bytecode {
+  // Collect definitions the way Runtime does: a later definition replaces an earlier one,
+  // and the top level statements become the body of main
+  let mut names: Vec<String> = vec![];
+  let mut top_level = vec![];
+  let mut top_level_main = false;
+  let mut bodies: HashMap<String, Body<'n>> = HashMap::new();
+  let mut define = |name: &str, body: Body<'n>| {
+    if !bodies.contains_key(name) {
+      names.push(name.to_string());
+    }
+    bodies.insert(name.to_string(), body);
+  };
+  let children = match node {
+    Node::Program{children} => children.as_slice(),
+    _ => slice::from_ref(node),
+  };
+  for n in children {
+    match n {
+      Node::FunctionDefine{children} => {
+        if let Some(Node::Identifier{value}) = children.first() {
+          define(value, Body{statements: children[1..].iter().collect(), returns_last: false});
+          top_level_main &= value != "main";
+        }
+      },
+      Node::Expression{..} |
+      Node::Statement{..} => {
+        top_level.push(n);
+        define("main", Body{statements: top_level.clone(), returns_last: true});
+        top_level_main = true;
+      },
+      _ => (),
+    }
+  }
+  let mut compiler = Compiler{
+    constants: vec![],
+    signatures: HashMap::new(),
+    positions,
+    lines: vec![],
+    index: 0,
+    slots: HashMap::new(),
+    code: vec![],
+    returns: None,
+    global: false,
+  };
+  for (index, name) in names.iter().enumerate() {
+    let params = match bodies[name].statements.first() {
+      Some(Node::FunctionArguments{children}) => children.iter().map(|param| match param {
+        Node::Expression{children} => match children.get(1) {
+          Some(Node::TypeAnnotation{value}) => Annotation::from_name(value),
+          _ => None,
+        },
+        _ => None,
+      }).collect(),
+      _ => vec![],
+    };
+    compiler.signatures.insert(name.clone(), Signature{index, params});
+  }
+  let functions = names.iter().enumerate().map(|(index, name)| {
+    compiler.global = top_level_main && name == "main";
+    compiler.function(index, name, &bodies[name])
+  }).collect();
+  Bytecode{
+    constants: compiler.constants,
+    functions,
+    main: names.iter().position(|name| name == "main"),
+    lines: compiler.lines,
+  }
+}
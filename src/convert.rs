@@ -0,0 +1,273 @@
+use crate::runtime::{RuntimeError, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+// Converts a Rust value into a script value, failing when it doesn't fit in one
+pub trait IntoValue {
+  fn into_value(self) -> Result<Value, RuntimeError>;
+}
+
+// Converts a script value into a Rust value, failing when it holds the wrong type
+pub trait FromValue: Sized {
+  fn from_value(value: Value) -> Result<Self, RuntimeError>;
+}
+
+impl IntoValue for Value {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    Ok(self)
+  }
+}
+
+impl FromValue for Value {
+  fn from_value(value: Value) -> Result<Value, RuntimeError> {
+    Ok(value)
+  }
+}
+
+// Numbers are i32, so only narrower integers convert into one without a check
+macro_rules! into_number {
+  ($($t:ty),*) => {$(
+    impl IntoValue for $t {
+      fn into_value(self) -> Result<Value, RuntimeError> {
+        Ok(Value::from(self))
+      }
+    }
+
+    impl From<$t> for Value {
+      fn from(value: $t) -> Value {
+        Value::Number(i32::from(value))
+      }
+    }
+  )*}
+}
+
+into_number!(i8, i16, i32, u8, u16);
+
+// Wider integers are checked, and fail the same way a Number that doesn't fit them does
+macro_rules! try_into_number {
+  ($($t:ty),*) => {$(
+    impl IntoValue for $t {
+      fn into_value(self) -> Result<Value, RuntimeError> {
+        Value::try_from(self)
+      }
+    }
+
+    impl TryFrom<$t> for Value {
+      type Error = RuntimeError;
+
+      fn try_from(value: $t) -> Result<Value, RuntimeError> {
+        i32::try_from(value).map(Value::Number).map_err(|_| "Number out of range")
+      }
+    }
+  )*}
+}
+
+try_into_number!(i64, isize, u32, u64, usize);
+
+macro_rules! from_number {
+  ($($t:ty),*) => {$(
+    impl FromValue for $t {
+      fn from_value(value: Value) -> Result<$t, RuntimeError> {
+        match value {
+          Value::Number(n) => <$t>::try_from(n).map_err(|_| "Number out of range"),
+          _ => Err("Expected a Number"),
+        }
+      }
+    }
+
+    impl TryFrom<Value> for $t {
+      type Error = RuntimeError;
+
+      fn try_from(value: Value) -> Result<$t, RuntimeError> {
+        <$t>::from_value(value)
+      }
+    }
+  )*}
+}
+
+from_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoValue for bool {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    Ok(Value::from(self))
+  }
+}
+
+impl FromValue for bool {
+  fn from_value(value: Value) -> Result<bool, RuntimeError> {
+    match value {
+      Value::Bool(value) => Ok(value),
+      _ => Err("Expected a Bool"),
+    }
+  }
+}
+
+impl IntoValue for String {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    Ok(Value::from(self))
+  }
+}
+
+impl IntoValue for &str {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    Ok(Value::from(self))
+  }
+}
+
+impl FromValue for String {
+  fn from_value(value: Value) -> Result<String, RuntimeError> {
+    match value {
+      Value::String(value) => Ok(value),
+      _ => Err("Expected a String"),
+    }
+  }
+}
+
+// Nil stands for None
+impl<T: IntoValue> IntoValue for Option<T> {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    match self {
+      Some(value) => value.into_value(),
+      None => Ok(Value::Nil),
+    }
+  }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+  fn from_value(value: Value) -> Result<Option<T>, RuntimeError> {
+    match value {
+      Value::Nil => Ok(None),
+      value => T::from_value(value).map(Some),
+    }
+  }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    self.into_iter().map(IntoValue::into_value).collect::<Result<_, _>>().map(Value::List)
+  }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+  fn from_value(value: Value) -> Result<Vec<T>, RuntimeError> {
+    match value {
+      Value::List(list) => list.into_iter().map(T::from_value).collect(),
+      _ => Err("Expected a List"),
+    }
+  }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+  fn into_value(self) -> Result<Value, RuntimeError> {
+    self.into_iter().map(|(key, value)| Ok((key, value.into_value()?))).collect::<Result<_, _>>().map(Value::Map)
+  }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+  fn from_value(value: Value) -> Result<HashMap<String, T>, RuntimeError> {
+    match value {
+      Value::Map(map) => map.into_iter().map(|(key, value)| Ok((key, T::from_value(value)?))).collect(),
+      _ => Err("Expected a Map"),
+    }
+  }
+}
+
+impl From<bool> for Value {
+  fn from(value: bool) -> Value {
+    Value::Bool(value)
+  }
+}
+
+impl From<String> for Value {
+  fn from(value: String) -> Value {
+    Value::String(value)
+  }
+}
+
+impl From<&str> for Value {
+  fn from(value: &str) -> Value {
+    Value::String(value.to_string())
+  }
+}
+
+// Collections convert without a check when what they hold does
+impl<T: Into<Value>> From<Option<T>> for Value {
+  fn from(value: Option<T>) -> Value {
+    value.map_or(Value::Nil, Into::into)
+  }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+  fn from(value: Vec<T>) -> Value {
+    Value::List(value.into_iter().map(Into::into).collect())
+  }
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+  fn from(value: HashMap<String, T>) -> Value {
+    Value::Map(value.into_iter().map(|(key, value)| (key, value.into())).collect())
+  }
+}
+
+impl TryFrom<Value> for bool {
+  type Error = RuntimeError;
+
+  fn try_from(value: Value) -> Result<bool, RuntimeError> {
+    bool::from_value(value)
+  }
+}
+
+impl TryFrom<Value> for String {
+  type Error = RuntimeError;
+
+  fn try_from(value: Value) -> Result<String, RuntimeError> {
+    String::from_value(value)
+  }
+}
+
+impl<T: FromValue> TryFrom<Value> for Vec<T> {
+  type Error = RuntimeError;
+
+  fn try_from(value: Value) -> Result<Vec<T>, RuntimeError> {
+    Vec::from_value(value)
+  }
+}
+
+impl<T: FromValue> TryFrom<Value> for HashMap<String, T> {
+  type Error = RuntimeError;
+
+  fn try_from(value: Value) -> Result<HashMap<String, T>, RuntimeError> {
+    HashMap::from_value(value)
+  }
+}
+
+// A Rust function with typed arguments, adapted to be called from scripts with Values.
+// Args is the tuple of argument types, which lets one closure type have a single impl.
+pub trait TypedFunction<Args> {
+  fn arity(&self) -> usize;
+  fn call(&self, args: &[Value]) -> Result<Value, RuntimeError>;
+}
+
+macro_rules! typed_function {
+  ($($arg:ident),*) => {
+    impl<F, R, $($arg),*> TypedFunction<($($arg,)*)> for F
+      where F: Fn($($arg),*) -> Result<R, RuntimeError>, R: IntoValue, $($arg: FromValue),* {
+      fn arity(&self) -> usize {
+        <[&str]>::len(&[$(stringify!($arg)),*])
+      }
+
+      #[allow(non_snake_case, unused_mut, unused_variables)]
+      fn call(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let mut args = args.iter().cloned();
+        $(let $arg = <$arg as FromValue>::from_value(args.next().ok_or("Wrong number of arguments")?)?;)*
+        self($($arg),*)?.into_value()
+      }
+    }
+  }
+}
+
+typed_function!();
+typed_function!(A);
+typed_function!(A, B);
+typed_function!(A, B, C);
+typed_function!(A, B, C, D);
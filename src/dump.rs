@@ -0,0 +1,144 @@
+use crate::parser::Node;
+use std::fmt::Write;
+
+enum Scalar<'a> {
+  String(&'a str),
+  Number(i64),
+  Bool(bool),
+}
+
+// A node taken apart into its variant name, its scalar fields and its lists of child
+// nodes, named and ordered as they are declared in Node
+struct Parts<'a> {
+  name: &'static str,
+  scalars: Vec<(&'static str, Scalar<'a>)>,
+  lists: Vec<(&'static str, &'a Vec<Node>)>,
+}
+
+fn parts(node: &Node) -> Parts<'_> {
+  let (name, scalars, lists) = match node {
+    Node::Program{children} => ("Program", vec![], vec![("children", children)]),
+    Node::Statement{children} => ("Statement", vec![], vec![("children", children)]),
+    Node::FunctionReturn{children} => ("FunctionReturn", vec![], vec![("children", children)]),
+    Node::FunctionDefine{children} => ("FunctionDefine", vec![], vec![("children", children)]),
+    Node::FunctionArguments{children} => ("FunctionArguments", vec![], vec![("children", children)]),
+    Node::FunctionStatements{children} => ("FunctionStatements", vec![], vec![("children", children)]),
+    Node::Expression{children} => ("Expression", vec![], vec![("children", children)]),
+    Node::MathExpression{name, children} => ("MathExpression", vec![("name", Scalar::String(name))], vec![("children", children)]),
+    Node::FunctionCall{name, children} => ("FunctionCall", vec![("name", Scalar::String(name))], vec![("children", children)]),
+    Node::VariableDefine{children} => ("VariableDefine", vec![], vec![("children", children)]),
+    Node::Number{value} => ("Number", vec![("value", Scalar::Number(i64::from(*value)))], vec![]),
+    Node::Bool{value} => ("Bool", vec![("value", Scalar::Bool(*value))], vec![]),
+    Node::Identifier{value} => ("Identifier", vec![("value", Scalar::String(value))], vec![]),
+    Node::Local{value, slot} => ("Local", vec![("value", Scalar::String(value)), ("slot", Scalar::Number(*slot as i64))], vec![]),
+    Node::String{value} => ("String", vec![("value", Scalar::String(value))], vec![]),
+    Node::Nil => ("Nil", vec![], vec![]),
+    Node::TypeAnnotation{value} => ("TypeAnnotation", vec![("value", Scalar::String(value))], vec![]),
+    Node::If{condition, children} => ("If", vec![], vec![("condition", condition), ("children", children)]),
+    Node::While{condition, children} => ("While", vec![], vec![("condition", condition), ("children", children)]),
+    Node::Condition{conditions} => ("Condition", vec![], vec![("conditions", conditions)]),
+    Node::ConditionExpression{name, children} => ("ConditionExpression", vec![("name", Scalar::String(name))], vec![("children", children)]),
+    Node::TestEquality{children} => ("TestEquality", vec![], vec![("children", children)]),
+  };
+  Parts{name, scalars, lists}
+}
+
+fn json_string(out: &mut String, value: &str) {
+  out.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => {
+        let _ = write!(out, "\\u{:04x}", c as u32);
+      },
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+fn scalar(out: &mut String, value: &Scalar) {
+  match value {
+    Scalar::String(value) => json_string(out, value),
+    Scalar::Number(value) => {
+      let _ = write!(out, "{}", value);
+    },
+    Scalar::Bool(value) => {
+      let _ = write!(out, "{}", value);
+    },
+  }
+}
+
+// One line per node, children indented under their parent. A node with more than one
+// list of children, such as an If, labels each list.
+pub fn outline(node: &Node) -> String {
+  let mut out = String::new();
+  outline_node(&mut out, node, 0);
+  out
+}
+
+fn outline_node(out: &mut String, node: &Node, depth: usize) {
+  let Parts{name, scalars, lists} = parts(node);
+  out.push_str(&"  ".repeat(depth));
+  out.push_str(name);
+  for (ix, (field, value)) in scalars.iter().enumerate() {
+    out.push(' ');
+    // The first field is what the node is about, so only the others are named
+    if ix > 0 {
+      let _ = write!(out, "{}=", field);
+    }
+    scalar(out, value);
+  }
+  out.push('\n');
+  let labelled = lists.len() > 1;
+  for (field, children) in lists {
+    let mut depth = depth + 1;
+    if labelled {
+      let _ = writeln!(out, "{}{}:", "  ".repeat(depth), field);
+      depth += 1;
+    }
+    for child in children {
+      outline_node(out, child, depth);
+    }
+  }
+}
+
+// The tree as JSON, one object per node. "type" is the variant name and every field of
+// the variant is a key of the same name, so {"type": "Number", "value": 1} or
+// {"type": "If", "condition": [...], "children": [...]}. Keys are in a fixed order and
+// indented two spaces a level, so dumps of two trees can be diffed.
+pub fn to_json(node: &Node) -> String {
+  let mut out = String::new();
+  json_node(&mut out, node, 0);
+  out.push('\n');
+  out
+}
+
+fn json_node(out: &mut String, node: &Node, depth: usize) {
+  let Parts{name, scalars, lists} = parts(node);
+  let indent = "  ".repeat(depth + 1);
+  out.push_str("{\n");
+  let _ = write!(out, "{}\"type\": ", indent);
+  json_string(out, name);
+  for (field, value) in &scalars {
+    let _ = write!(out, ",\n{}\"{}\": ", indent, field);
+    scalar(out, value);
+  }
+  for (field, children) in lists {
+    let _ = write!(out, ",\n{}\"{}\": [", indent, field);
+    for (ix, child) in children.iter().enumerate() {
+      out.push_str(if ix == 0 { "\n" } else { ",\n" });
+      out.push_str(&"  ".repeat(depth + 2));
+      json_node(out, child, depth + 2);
+    }
+    if !children.is_empty() {
+      let _ = write!(out, "\n{}", indent);
+    }
+    out.push(']');
+  }
+  let _ = write!(out, "\n{}}}", "  ".repeat(depth));
+}
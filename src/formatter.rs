@@ -0,0 +1,337 @@
+use crate::parser::Node;
+use crate::syntax::{SyntaxElement, SyntaxKind, SyntaxNode, TokenKind};
+
+// Turns a tree back into source in one canonical layout: two space indents, one item per
+// line, spaces around operators and only the parentheses precedence requires. Parsing
+// the result gives back the same tree.
+pub fn format(node: &Node) -> String {
+  let mut formatter = Formatter{out: String::new(), depth: 0};
+  match node {
+    Node::Program{children} => formatter.program(children, &Layout::default()),
+    node => formatter.item(node, None),
+  }
+  formatter.out
+}
+
+// The same layout, but keeping the comments and blank lines found in the syntax tree the
+// program was parsed with. A comment on its own line stays on a line of its own before
+// the item after it, and one after an item stays at the end of that item's line. Runs
+// of blank lines become one.
+pub fn format_syntax(node: &Node, tree: &SyntaxNode) -> String {
+  let mut formatter = Formatter{out: String::new(), depth: 0};
+  match node {
+    Node::Program{children} => formatter.program(children, &layout(Some(tree))),
+    node => formatter.item(node, tree.nodes().next()),
+  }
+  formatter.out
+}
+
+struct Formatter {
+  out: String,
+  depth: usize,
+}
+
+// What goes on the lines between the items of a block
+enum Piece<'a> {
+  Blank,
+  Comment(&'a str),
+}
+
+// The trivia of a block, or of a whole program, sorted out around its items. Without a
+// syntax tree everything is empty.
+#[derive(Default)]
+struct Layout<'a> {
+  // The syntax node of each item
+  items: Vec<&'a SyntaxNode>,
+  // What comes before each item, and the comment after it on the same line
+  before: Vec<Vec<Piece<'a>>>,
+  after: Vec<Option<&'a str>>,
+  // A comment on the line that opens the block
+  opening: Option<&'a str>,
+  // What comes after the last item
+  end: Vec<Piece<'a>>,
+}
+
+// Items are the nodes after the opening brace, or every node of a program
+fn layout(tree: Option<&SyntaxNode>) -> Layout<'_> {
+  let mut layout = Layout::default();
+  let tree = match tree {
+    Some(tree) => tree,
+    None => return layout,
+  };
+  let mut inside = tree.kind == SyntaxKind::Program;
+  // How many line breaks since the last item or comment, which is None at the start
+  let mut newlines = None;
+  let mut pieces = vec![];
+  for child in &tree.children {
+    match child {
+      SyntaxElement::Token(token) if token.text == "{" && !inside => {
+        inside = true;
+        newlines = Some(0);
+      },
+      SyntaxElement::Token(_) | SyntaxElement::Node(_) if !inside => (),
+      SyntaxElement::Token(token) if token.kind == TokenKind::Whitespace => {
+        newlines = newlines.map(|n| n + token.text.matches('\n').count());
+      },
+      SyntaxElement::Token(token) if token.kind == TokenKind::Comment => {
+        match newlines {
+          Some(0) if layout.items.is_empty() => layout.opening = Some(&token.text),
+          Some(0) => *layout.after.last_mut().unwrap() = Some(&token.text),
+          _ => {
+            if newlines > Some(1) && !(layout.items.is_empty() && pieces.is_empty()) {
+              pieces.push(Piece::Blank);
+            }
+            pieces.push(Piece::Comment(&token.text));
+          },
+        }
+        newlines = Some(0);
+      },
+      SyntaxElement::Node(node) => {
+        if newlines > Some(1) && !(layout.items.is_empty() && pieces.is_empty()) {
+          pieces.push(Piece::Blank);
+        }
+        layout.items.push(node);
+        layout.before.push(std::mem::take(&mut pieces));
+        layout.after.push(None);
+        newlines = Some(0);
+      },
+      SyntaxElement::Token(_) => break,
+    }
+  }
+  layout.end = pieces;
+  layout
+}
+
+// How tightly an operand binds: + - are l1, * / are l2, ^ is l3 and anything else is an
+// operand that never needs parentheses
+fn level(node: &Node) -> usize {
+  match node {
+    Node::MathExpression{name, children} if children.len() == 2 => match name.as_ref() {
+      "+" | "-" => 1,
+      "*" | "/" => 2,
+      _ => 3,
+    },
+    _ => 4,
+  }
+}
+
+impl Formatter {
+
+  fn line(&mut self, text: &str) {
+    self.out.push_str(&"  ".repeat(self.depth));
+    self.out.push_str(text);
+    self.out.push('\n');
+  }
+
+  // Ends the last line with a comment
+  fn trailing(&mut self, comment: &str) {
+    self.out.pop();
+    self.out.push(' ');
+    self.out.push_str(comment);
+    self.out.push('\n');
+  }
+
+  // A blank line, unless there is one already or nothing before it
+  fn blank(&mut self) {
+    if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+      self.out.push('\n');
+    }
+  }
+
+  fn pieces(&mut self, pieces: &[Piece]) {
+    for piece in pieces {
+      match piece {
+        Piece::Blank => self.blank(),
+        Piece::Comment(comment) => self.line(comment),
+      }
+    }
+  }
+
+  // The items of a block or program with the trivia around them
+  fn items(&mut self, children: &[Node], layout: &Layout, blank_before: impl Fn(usize) -> bool) {
+    if let Some(comment) = layout.opening {
+      self.trailing(comment);
+    }
+    for (ix, node) in children.iter().enumerate() {
+      if blank_before(ix) {
+        self.blank();
+      }
+      if let Some(pieces) = layout.before.get(ix) {
+        self.pieces(pieces);
+      }
+      self.item(node, layout.items.get(ix).copied());
+      if let Some(Some(comment)) = layout.after.get(ix) {
+        self.trailing(comment);
+      }
+    }
+    self.pieces(&layout.end);
+  }
+
+  // Functions are set apart from their neighbours by a blank line
+  fn program(&mut self, children: &[Node], layout: &Layout) {
+    let function = |node: &Node| matches!(node, Node::FunctionDefine{..});
+    self.items(children, layout, |ix| ix > 0 && (function(&children[ix]) || function(&children[ix - 1])));
+  }
+
+  // A function definition, statement or block, on lines of its own
+  fn item(&mut self, node: &Node, tree: Option<&SyntaxNode>) {
+    match node {
+      Node::FunctionDefine{children} => self.function(children, tree),
+      Node::If{condition, children} => self.if_chain(condition, children, tree),
+      Node::While{condition, children} => {
+        let head = format!("while {} {{", self.condition(&condition[0]));
+        self.line(&head);
+        self.block(children, &layout(tree));
+        self.line("}");
+      },
+      // An if at the top level is parsed as an expression
+      Node::Expression{children} if matches!(children.first(), Some(Node::If{..})) => self.item(&children[0], tree.and_then(|tree| tree.nodes().next())),
+      Node::Statement{children} => {
+        let text = format!("{};", self.statement(&children[0]));
+        self.line(&text);
+      },
+      node => {
+        let text = self.expression(node);
+        self.line(&text);
+      },
+    }
+  }
+
+  fn block(&mut self, statements: &[Node], layout: &Layout) {
+    self.depth += 1;
+    self.items(statements, layout, |_| false);
+    self.depth -= 1;
+  }
+
+  fn function(&mut self, children: &[Node], tree: Option<&SyntaxNode>) {
+    let mut params = vec![];
+    let mut returns = String::new();
+    let mut statements = vec![];
+    for child in &children[1..] {
+      match child {
+        Node::FunctionArguments{children} => params = children.iter().map(|param| self.parameter(param)).collect(),
+        Node::TypeAnnotation{value} => returns = format!(" -> {}", value),
+        node => statements.push(node.clone()),
+      }
+    }
+    let head = format!("fn {}({}){} {{", self.expression(&children[0]), params.join(", "), returns);
+    self.line(&head);
+    self.block(&statements, &layout(tree));
+    self.line("}");
+  }
+
+  fn parameter(&self, node: &Node) -> String {
+    match node {
+      Node::Expression{children} => match children.get(1) {
+        Some(Node::TypeAnnotation{value}) => format!("{}: {}", self.expression(&children[0]), value),
+        _ => self.expression(&children[0]),
+      },
+      node => self.expression(node),
+    }
+  }
+
+  // Each condition starts a branch, and a body left over at the end is the else
+  fn if_chain(&mut self, condition: &[Node], children: &[Node], tree: Option<&SyntaxNode>) {
+    let mut bodies = tree.into_iter().flat_map(|tree| tree.nodes()).filter(|node| node.kind == SyntaxKind::FunctionStatements);
+    for (ix, body) in children.iter().enumerate() {
+      let head = match (ix, condition.get(ix)) {
+        (0, Some(c)) => format!("if {} {{", self.condition(c)),
+        (_, Some(c)) => format!("}} else if {} {{", self.condition(c)),
+        (_, None) => "} else {".to_string(),
+      };
+      self.line(&head);
+      let layout = layout(bodies.next());
+      match body {
+        Node::FunctionStatements{children} => self.block(children, &layout),
+        node => self.block(std::slice::from_ref(node), &layout),
+      }
+    }
+    self.line("}");
+  }
+
+  fn statement(&self, node: &Node) -> String {
+    match node {
+      Node::VariableDefine{children} => {
+        let annotation = match children.get(2) {
+          Some(Node::TypeAnnotation{value}) => format!(": {}", value),
+          _ => String::new(),
+        };
+        format!("let {}{} = {}", self.expression(&children[0]), annotation, self.expression(&children[1]))
+      },
+      Node::FunctionReturn{children} => match children.first() {
+        Some(value) => format!("return {}", self.expression(value)),
+        None => "return".to_string(),
+      },
+      node => self.expression(node),
+    }
+  }
+
+  // The condition of an if or while, which needs no parentheses of its own
+  fn condition(&self, node: &Node) -> String {
+    match node {
+      Node::Condition{conditions} if conditions.len() == 1 => self.expression(&conditions[0]),
+      node => self.expression(node),
+    }
+  }
+
+  fn expression(&self, node: &Node) -> String {
+    match node {
+      Node::Expression{children} => children.first().map(|child| self.expression(child)).unwrap_or_default(),
+      Node::MathExpression{name, children} if children.len() == 2 => {
+        // + - and ^ group to the left and * / to the right, so an operand of the same
+        // level only goes without parentheses on the side the operator groups from
+        let level = level(node);
+        let (left, right) = if level == 2 { (level + 1, level) } else { (level, level + 1) };
+        format!("{} {} {}", self.operand(&children[0], left), name, self.operand(&children[1], right))
+      },
+      Node::FunctionCall{name, children} => {
+        let args = match children.first() {
+          Some(Node::FunctionArguments{children}) => children,
+          _ => children,
+        };
+        let args: Vec<String> = args.iter().map(|arg| self.expression(arg)).collect();
+        format!("{}({})", name, args.join(", "))
+      },
+      Node::Number{value} => value.to_string(),
+      Node::Bool{value} => value.to_string(),
+      Node::Nil => "nil".to_string(),
+      Node::String{value} => format!("\"{}\"", value),
+      Node::Identifier{value} |
+      Node::Local{value, ..} => value.clone(),
+      // A parenthesized condition is parsed as a Condition of its own
+      Node::Condition{conditions} => format!("({})", self.expression(&conditions[0])),
+      Node::ConditionExpression{name, children} if name == "!" => format!("!{}", self.expression(&children[0])),
+      Node::ConditionExpression{name, children} => format!("{} {} {}", self.expression(&children[0]), name, self.expression(&children[1])),
+      Node::TestEquality{children} => format!("{} == {}", self.expression(&children[0]), self.expression(&children[1])),
+      Node::VariableDefine{..} |
+      Node::FunctionReturn{..} => self.statement(node),
+      Node::TypeAnnotation{value} => value.clone(),
+      Node::FunctionArguments{children} => children.iter().map(|child| self.expression(child)).collect::<Vec<_>>().join(", "),
+      Node::MathExpression{name, children} => children.iter().map(|child| self.expression(child)).collect::<Vec<_>>().join(&format!(" {} ", name)),
+      // Anything else spans lines, such as an if used as a value
+      Node::Program{..} |
+      Node::FunctionStatements{..} |
+      Node::FunctionDefine{..} |
+      Node::Statement{..} |
+      Node::If{..} |
+      Node::While{..} => {
+        let mut formatter = Formatter{out: String::new(), depth: self.depth};
+        match node {
+          Node::Program{children} => formatter.program(children, &Layout::default()),
+          Node::FunctionStatements{children} => children.iter().for_each(|child| formatter.item(child, None)),
+          node => formatter.item(node, None),
+        }
+        formatter.out.trim().to_string()
+      },
+    }
+  }
+
+  fn operand(&self, node: &Node, min_level: usize) -> String {
+    if level(node) < min_level {
+      format!("({})", self.expression(node))
+    } else {
+      self.expression(node)
+    }
+  }
+
+}
@@ -0,0 +1,84 @@
+use crate::lexer::{tokenize, TYPE_NAMES};
+use crate::syntax::TokenKind;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+  Binary,
+  Octal,
+  Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+  Whitespace,
+  Comment,
+  Keyword,
+  Identifier,
+  // The name of a function being defined or called
+  FunctionName,
+  Number(Base),
+  String,
+  Operator,
+  Punctuation,
+  // Text the lexer doesn't accept
+  Invalid,
+}
+
+impl TokenClass {
+  // The TextMate scope for the class, which also serves as a CSS class name
+  pub fn scope(self) -> &'static str {
+    match self {
+      TokenClass::Whitespace => "text",
+      TokenClass::Comment => "comment.line.double-slash",
+      TokenClass::Keyword => "keyword",
+      TokenClass::Identifier => "variable",
+      TokenClass::FunctionName => "entity.name.function",
+      TokenClass::Number(Base::Binary) => "constant.numeric.binary",
+      TokenClass::Number(Base::Octal) => "constant.numeric.octal",
+      TokenClass::Number(Base::Decimal) => "constant.numeric.decimal",
+      TokenClass::String => "string.quoted.double",
+      TokenClass::Operator => "keyword.operator",
+      TokenClass::Punctuation => "punctuation",
+      TokenClass::Invalid => "invalid.illegal",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticToken {
+  pub class: TokenClass,
+  pub span: Range<usize>,
+}
+
+// Classifies every character of the source, in order, whether or not it parses. The
+// tokens are the ones the parser reads, refined by where they are: a name is a function
+// name when a call's `(` comes next, and a type name is a keyword after the `:` or `->`
+// of an annotation.
+pub fn semantic_tokens(source: &str) -> Vec<SemanticToken> {
+  let tokens = tokenize(source);
+  let significant: Vec<usize> = (0..tokens.len()).filter(|&ix| !tokens[ix].kind.is_trivia()).collect();
+  let mut classes = vec![];
+  for (ix, token) in tokens.iter().enumerate() {
+    let position = significant.binary_search(&ix);
+    let before = position.ok().and_then(|p| p.checked_sub(1)).map(|p| tokens[significant[p]].text.as_str());
+    let after = position.ok().and_then(|p| significant.get(p + 1)).map(|&p| tokens[p].text.as_str());
+    let class = match token.kind {
+      TokenKind::Whitespace => TokenClass::Whitespace,
+      TokenKind::Comment => TokenClass::Comment,
+      TokenKind::Keyword => TokenClass::Keyword,
+      TokenKind::Identifier if matches!(before, Some(":" | "->")) && TYPE_NAMES.contains(&token.text.as_str()) => TokenClass::Keyword,
+      TokenKind::Identifier if after == Some("(") => TokenClass::FunctionName,
+      TokenKind::Identifier => TokenClass::Identifier,
+      TokenKind::Number if token.text.starts_with("0b") => TokenClass::Number(Base::Binary),
+      TokenKind::Number if token.text.starts_with("0o") => TokenClass::Number(Base::Octal),
+      TokenKind::Number => TokenClass::Number(Base::Decimal),
+      TokenKind::String => TokenClass::String,
+      TokenKind::Operator => TokenClass::Operator,
+      TokenKind::Punctuation => TokenClass::Punctuation,
+      TokenKind::Invalid => TokenClass::Invalid,
+    };
+    classes.push(SemanticToken{class, span: token.offset..token.offset + token.text.len()});
+  }
+  classes
+}
@@ -0,0 +1,101 @@
+use crate::convert::TypedFunction;
+use crate::output::Output;
+use crate::parser::Node;
+use crate::resolver::{resolve_with_hosts, ResolveError};
+use crate::runtime::{Host, Meter, RunLimits, Runtime, RuntimeError, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Runs scripts on behalf of a host program. The host registers native functions for
+// scripts to call, and can call the functions a script defines. Functions and globals
+// stay defined between runs, so a program can be fed in as a series of snippets.
+pub struct Interpreter {
+  runtime: Runtime,
+  limits: RunLimits,
+}
+
+impl Default for Interpreter {
+  fn default() -> Interpreter {
+    Interpreter::new()
+  }
+}
+
+impl Interpreter {
+
+  pub fn new() -> Interpreter {
+    let limits = RunLimits::default();
+    Interpreter {
+      runtime: Runtime::new(&limits),
+      limits,
+    }
+  }
+
+  // Limits applied to each run or call from here on
+  pub fn limits(mut self, limits: RunLimits) -> Interpreter {
+    self.limits = limits;
+    self
+  }
+
+  // Where print and println write, stdout by default
+  pub fn output(mut self, output: Output) -> Interpreter {
+    self.runtime.output = output;
+    self
+  }
+
+  // Registers a native function scripts can call by name with exactly arity arguments.
+  // A function the script defines with the same name takes its place.
+  pub fn function<F>(mut self, name: &str, arity: usize, function: F) -> Interpreter
+    where F: Fn(&[Value]) -> Result<Value, RuntimeError> + 'static {
+    self.runtime.host.insert(name.to_string(), Host{arity, function: Rc::new(function)});
+    self
+  }
+
+  // Registers a function with typed arguments and result, such as
+  // `|a: i32, b: String| Ok(format!("{}{}", b, a))`. Its arity comes from its arguments,
+  // and arguments of the wrong type fail the call with a runtime error.
+  pub fn typed_function<F, Args>(self, name: &str, function: F) -> Interpreter
+    where F: TypedFunction<Args> + 'static {
+    let arity = function.arity();
+    self.function(name, arity, move |args| function.call(args))
+  }
+
+  // Sets a global. Scripts can read a global anywhere a variable of the same name isn't
+  // bound, and the program's top level lets set them.
+  pub fn set_global(&mut self, name: &str, value: Value) {
+    self.runtime.globals.insert(name.to_string(), value);
+  }
+
+  pub fn global(&self, name: &str) -> Option<&Value> {
+    self.runtime.globals.get(name)
+  }
+
+  pub fn globals(&self) -> &HashMap<String, Value> {
+    &self.runtime.globals
+  }
+
+  // Resolves a program against the functions registered here, so it can call them
+  pub fn resolve(&self, program: &Node) -> Result<Node, Vec<ResolveError>> {
+    let hosts: Vec<&str> = self.runtime.host.keys().map(String::as_str).collect();
+    resolve_with_hosts(program, &hosts)
+  }
+
+  // Defines the program's functions without running it
+  pub fn load(&mut self, program: &Node) -> Result<(), RuntimeError> {
+    self.runtime.run(program)?;
+    Ok(())
+  }
+
+  // Defines the program's functions and runs main. Only this program's top level
+  // statements make up main, so an earlier program's don't run again.
+  pub fn run(&mut self, program: &Node) -> Result<Value, RuntimeError> {
+    self.load(program)?;
+    self.call("main", &[])
+  }
+
+  // Calls a function defined by a loaded program, or registered by the host
+  pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+    self.runtime.meter = Meter::new(&self.limits);
+    self.runtime.call_values(name, args)
+  }
+
+}
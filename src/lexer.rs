@@ -0,0 +1,90 @@
+use crate::syntax::{Token, TokenKind};
+
+// Words that can never be names. Type names aren't reserved, since they only mean a type
+// after the `:` or `->` of an annotation.
+pub const KEYWORDS: &[&str] = &["fn", "let", "return", "if", "else", "while", "true", "false", "nil"];
+pub const TYPE_NAMES: &[&str] = &["int", "bool", "string", "nil"];
+// Longest first, so `==` is never read as two `=`
+pub const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "&&", "||", "<", ">", "&", "|", "!", "+", "-", "*", "/", "^", "="];
+// Checked before the operators, so `->` isn't a minus
+pub const PUNCTUATION: &[&str] = &["->", "(", ")", "{", "}", ",", ";", ":"];
+
+// Splits the whole source into tokens, trivia included, so every character is in exactly
+// one of them. Anything the language doesn't allow becomes an Invalid token, which the
+// parser never accepts.
+pub fn tokenize(source: &str) -> Vec<Token> {
+  let mut tokens = vec![];
+  let mut offset = 0;
+  while offset < source.len() {
+    let (kind, len) = next_token(&source[offset..]);
+    tokens.push(Token{kind, text: source[offset..offset + len].to_string(), offset});
+    offset += len;
+  }
+  tokens
+}
+
+fn is_word(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+// The kind and length of the token rest starts with
+pub(crate) fn next_token(rest: &str) -> (TokenKind, usize) {
+  let first = match rest.chars().next() {
+    Some(c) => c,
+    None => return (TokenKind::Invalid, 0),
+  };
+  let space = rest.len() - rest.trim_start_matches(&[' ', '\t', '\n', '\r'][..]).len();
+  if space > 0 {
+    return (TokenKind::Whitespace, space);
+  }
+  if rest.starts_with("//") {
+    return (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()));
+  }
+  // Names start with a letter or underscore and go on with letters, digits and underscores
+  if first.is_ascii_alphabetic() || first == '_' {
+    let len = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+    let kind = if KEYWORDS.contains(&&rest[..len]) { TokenKind::Keyword } else { TokenKind::Identifier };
+    return (kind, len);
+  }
+  // A word starting with a digit is one number, or invalid as a whole, so `12abc` isn't a
+  // number followed by a name
+  if first.is_ascii_digit() {
+    let len = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+    let kind = if number_value(&rest[..len]).is_some() { TokenKind::Number } else { TokenKind::Invalid };
+    return (kind, len);
+  }
+  // Strings hold letters, digits and spaces, and end on the line they start on
+  if first == '"' {
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    return match line[1..].find('"') {
+      Some(end) if end > 0 && line[1..end + 1].chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') => (TokenKind::String, end + 2),
+      Some(end) => (TokenKind::Invalid, end + 2),
+      None => (TokenKind::Invalid, line.len()),
+    };
+  }
+  if let Some(p) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) {
+    return (TokenKind::Punctuation, p.len());
+  }
+  if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+    return (TokenKind::Operator, op.len());
+  }
+  (TokenKind::Invalid, first.len_utf8())
+}
+
+// The value of a number literal: 0b binary, 0o octal, or decimal without leading zeros.
+// None when it isn't one, or doesn't fit.
+pub(crate) fn number_value(text: &str) -> Option<i32> {
+  let (digits, radix) = if let Some(digits) = text.strip_prefix("0b") {
+    (digits, 2)
+  } else if let Some(digits) = text.strip_prefix("0o") {
+    (digits, 8)
+  } else if text.len() > 1 && text.starts_with('0') {
+    return None;
+  } else {
+    (text, 10)
+  };
+  if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+    return None;
+  }
+  i32::from_str_radix(digits, radix).ok()
+}
@@ -1,7 +1,38 @@
 extern crate nom;
 
+mod lexer;
 mod parser;
+mod syntax;
 mod runtime;
+mod checker;
+mod resolver;
+mod bytecode;
+mod compiler;
+mod vm;
+mod interpreter;
+mod convert;
+mod builtins;
+mod output;
+mod dump;
+mod formatter;
+mod optimizer;
+mod analysis;
+mod highlight;
 
-pub use self::parser::{program, Node};
-pub use self::runtime::{Value, run};
\ No newline at end of file
+pub use self::lexer::{tokenize, KEYWORDS, OPERATORS, PUNCTUATION, TYPE_NAMES};
+pub use self::parser::{program, program_with_syntax, parse, parse_with_syntax, parse_error, Node, ParseError};
+pub use self::syntax::{SyntaxElement, SyntaxKind, SyntaxNode, Spans, Token, TokenKind};
+pub use self::dump::{outline, to_json};
+pub use self::formatter::{format, format_syntax};
+pub use self::runtime::{Value, RunLimits, RuntimeError, HostFunction, run, run_with_limits, DEFAULT_MAX_DEPTH};
+pub use self::interpreter::Interpreter;
+pub use self::output::{Output, OutputBuffer};
+pub use self::convert::{FromValue, IntoValue, TypedFunction};
+pub use self::checker::{check, infer_types, Type, TypeError, Types};
+pub use self::resolver::{resolve, resolve_with_hosts, ResolveError};
+pub use self::bytecode::{Annotation, Bytecode, Function, Instruction, LineInfo, LoadError, crc32, MAGIC};
+pub use self::compiler::{compile, compile_with_lines};
+pub use self::vm::{execute, execute_with_limits, execute_with_output, execute_with_globals};
+pub use self::analysis::{Analysis, Completion, CompletionKind, Diagnostic, Severity, Symbol};
+pub use self::highlight::{semantic_tokens, Base, SemanticToken, TokenClass};
+pub use self::optimizer::{optimize, fold_constants, eliminate_dead_branches, remove_unused_lets, inline_trivial_functions};
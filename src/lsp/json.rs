@@ -0,0 +1,278 @@
+use std::fmt;
+
+// Just enough JSON for the protocol. Objects keep their keys in order, so what is written
+// is predictable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+  }
+
+  // Looks up a path of keys through nested objects
+  pub fn get(&self, path: &[&str]) -> Option<&Json> {
+    let mut value = self;
+    for key in path {
+      value = match value {
+        Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)?,
+        _ => return None,
+      };
+    }
+    Some(value)
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_usize(&self) -> Option<usize> {
+    match self {
+      Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+      _ => None,
+    }
+  }
+
+  pub fn parse(text: &str) -> Result<Json, &'static str> {
+    let mut parser = Parser{text: text.as_bytes(), pos: 0};
+    let value = parser.value()?;
+    parser.space();
+    if parser.pos != text.len() {
+      return Err("Trailing characters");
+    }
+    Ok(value)
+  }
+}
+
+impl From<&str> for Json {
+  fn from(s: &str) -> Json {
+    Json::String(s.to_string())
+  }
+}
+
+impl From<String> for Json {
+  fn from(s: String) -> Json {
+    Json::String(s)
+  }
+}
+
+impl From<usize> for Json {
+  fn from(n: usize) -> Json {
+    Json::Number(n as f64)
+  }
+}
+
+impl From<bool> for Json {
+  fn from(b: bool) -> Json {
+    Json::Bool(b)
+  }
+}
+
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Json::Null => write!(f, "null"),
+      Json::Bool(b) => write!(f, "{}", b),
+      Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+      Json::Number(n) => write!(f, "{}", n),
+      Json::String(s) => string(f, s),
+      Json::Array(items) => {
+        write!(f, "[")?;
+        for (ix, item) in items.iter().enumerate() {
+          if ix > 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{}", item)?;
+        }
+        write!(f, "]")
+      },
+      Json::Object(fields) => {
+        write!(f, "{{")?;
+        for (ix, (key, value)) in fields.iter().enumerate() {
+          if ix > 0 {
+            write!(f, ",")?;
+          }
+          string(f, key)?;
+          write!(f, ":{}", value)?;
+        }
+        write!(f, "}}")
+      },
+    }
+  }
+}
+
+fn string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  write!(f, "\"")
+}
+
+struct Parser<'a> {
+  text: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+
+  fn space(&mut self) {
+    while matches!(self.text.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+      self.pos += 1;
+    }
+  }
+
+  fn literal(&mut self, word: &str, value: Json) -> Result<Json, &'static str> {
+    if self.text[self.pos..].starts_with(word.as_bytes()) {
+      self.pos += word.len();
+      Ok(value)
+    } else {
+      Err("Unexpected character")
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, &'static str> {
+    self.space();
+    match self.text.get(self.pos) {
+      Some(b'n') => self.literal("null", Json::Null),
+      Some(b't') => self.literal("true", Json::Bool(true)),
+      Some(b'f') => self.literal("false", Json::Bool(false)),
+      Some(b'"') => Ok(Json::String(self.string()?)),
+      Some(b'[') => {
+        self.pos += 1;
+        let mut items = vec![];
+        self.space();
+        if self.text.get(self.pos) == Some(&b']') {
+          self.pos += 1;
+          return Ok(Json::Array(items));
+        }
+        loop {
+          items.push(self.value()?);
+          self.space();
+          match self.text.get(self.pos) {
+            Some(b',') => self.pos += 1,
+            Some(b']') => {
+              self.pos += 1;
+              return Ok(Json::Array(items));
+            },
+            _ => return Err("Expected , or ]"),
+          }
+        }
+      },
+      Some(b'{') => {
+        self.pos += 1;
+        let mut fields = vec![];
+        self.space();
+        if self.text.get(self.pos) == Some(&b'}') {
+          self.pos += 1;
+          return Ok(Json::Object(fields));
+        }
+        loop {
+          self.space();
+          if self.text.get(self.pos) != Some(&b'"') {
+            return Err("Expected a key");
+          }
+          let key = self.string()?;
+          self.space();
+          if self.text.get(self.pos) != Some(&b':') {
+            return Err("Expected :");
+          }
+          self.pos += 1;
+          fields.push((key, self.value()?));
+          self.space();
+          match self.text.get(self.pos) {
+            Some(b',') => self.pos += 1,
+            Some(b'}') => {
+              self.pos += 1;
+              return Ok(Json::Object(fields));
+            },
+            _ => return Err("Expected , or }"),
+          }
+        }
+      },
+      Some(b'-' | b'0'..=b'9') => {
+        let start = self.pos;
+        while matches!(self.text.get(self.pos), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+          self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.text[start..self.pos]).map_err(|_| "Bad number")?;
+        number.parse().map(Json::Number).map_err(|_| "Bad number")
+      },
+      _ => Err("Unexpected character"),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, &'static str> {
+    self.pos += 1;
+    let mut bytes = vec![];
+    loop {
+      match self.text.get(self.pos) {
+        None => return Err("Unterminated string"),
+        Some(b'"') => {
+          self.pos += 1;
+          return String::from_utf8(bytes).map_err(|_| "Bad string");
+        },
+        Some(b'\\') => {
+          let escaped = *self.text.get(self.pos + 1).ok_or("Unterminated string")?;
+          self.pos += 2;
+          let c = match escaped {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => self.unicode()?,
+            _ => return Err("Bad escape"),
+          };
+          let mut buffer = [0; 4];
+          bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+        },
+        Some(&byte) => {
+          bytes.push(byte);
+          self.pos += 1;
+        },
+      }
+    }
+  }
+
+  // A \u escape, which is two of them for a character outside the basic plane
+  fn unicode(&mut self) -> Result<char, &'static str> {
+    let unit = self.hex()?;
+    if (0xd800..0xdc00).contains(&unit) && self.text[self.pos..].starts_with(b"\\u") {
+      self.pos += 2;
+      let low = self.hex()?;
+      let c = 0x10000 + ((unit - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+      return char::from_u32(c).ok_or("Bad escape");
+    }
+    char::from_u32(unit).ok_or("Bad escape")
+  }
+
+  fn hex(&mut self) -> Result<u32, &'static str> {
+    let digits = self.text.get(self.pos..self.pos + 4).ok_or("Bad escape")?;
+    let digits = std::str::from_utf8(digits).map_err(|_| "Bad escape")?;
+    self.pos += 4;
+    u32::from_str_radix(digits, 16).map_err(|_| "Bad escape")
+  }
+
+}
@@ -0,0 +1,259 @@
+extern crate cse262_project;
+
+mod json;
+
+use cse262_project::{Analysis, CompletionKind, Severity};
+use json::Json;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::process;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i32 = -32700;
+const INVALID_PARAMS: i32 = -32602;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+// Protocol numbers for the kinds of things the server reports
+const SEVERITY_ERROR: usize = 1;
+const SEVERITY_WARNING: usize = 2;
+const SYMBOL_FUNCTION: usize = 12;
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_VARIABLE: usize = 6;
+// Documents are sent whole on every change
+const SYNC_FULL: usize = 1;
+
+// A message is a Content-Length header, a blank line and that many bytes of JSON. None
+// means the input has ended.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() && length.is_some() {
+      break;
+    }
+    if let Some(value) = line.strip_prefix("Content-Length:") {
+      length = value.trim().parse::<usize>().ok();
+    }
+  }
+  let mut body = vec![0; length.unwrap_or_default()];
+  input.read_exact(&mut body)?;
+  String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(message: Json) {
+  let body = message.to_string();
+  let stdout = io::stdout();
+  let mut stdout = stdout.lock();
+  let _ = write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+  let _ = stdout.flush();
+}
+
+// Positions are a line and a count of UTF-16 code units into it
+fn position(text: &str, offset: usize) -> Json {
+  let before = &text[..offset];
+  let start = before.rfind('\n').map_or(0, |ix| ix + 1);
+  let character: usize = before[start..].chars().map(char::len_utf16).sum();
+  Json::object(vec![("line", before.matches('\n').count().into()), ("character", character.into())])
+}
+
+fn range(text: &str, span: &Range<usize>) -> Json {
+  Json::object(vec![("start", position(text, span.start)), ("end", position(text, span.end))])
+}
+
+// The byte offset of a position, clamped to the end of its line
+fn offset(text: &str, position: &Json) -> Option<usize> {
+  let line = position.get(&["line"])?.as_usize()?;
+  let character = position.get(&["character"])?.as_usize()?;
+  let start = match line {
+    0 => 0,
+    line => text.match_indices('\n').nth(line - 1)?.0 + 1,
+  };
+  let mut units = 0;
+  for (ix, c) in text[start..].char_indices() {
+    if units >= character || c == '\n' {
+      return Some(start + ix);
+    }
+    units += c.len_utf16();
+  }
+  Some(text.len())
+}
+
+struct Document {
+  text: String,
+  analysis: Analysis,
+}
+
+// An error code and message to answer a request with
+type Failure = (i32, &'static str);
+
+#[derive(Default)]
+struct Server {
+  documents: HashMap<String, Document>,
+  shutdown: bool,
+}
+
+impl Server {
+
+  fn handle(&mut self, message: &Json) {
+    let method = message.get(&["method"]).and_then(Json::as_str);
+    let params = message.get(&["params"]).cloned().unwrap_or(Json::Null);
+    // Anything without a method is a response to the server, which asks nothing
+    match (method, message.get(&["id"])) {
+      (Some(method), Some(id)) => {
+        let mut reply = vec![("jsonrpc", "2.0".into()), ("id", id.clone())];
+        match self.request(method, &params) {
+          Ok(result) => reply.push(("result", result)),
+          Err((code, message)) => reply.push(("error", Json::object(vec![("code", Json::Number(f64::from(code))), ("message", message.into())]))),
+        }
+        write_message(Json::object(reply));
+      },
+      (Some(method), None) => self.notification(method, &params),
+      (None, _) => (),
+    }
+  }
+
+  fn request(&mut self, method: &str, params: &Json) -> Result<Json, Failure> {
+    match method {
+      "initialize" => Ok(Json::object(vec![
+        ("capabilities", Json::object(vec![
+          ("textDocumentSync", SYNC_FULL.into()),
+          ("definitionProvider", true.into()),
+          ("hoverProvider", true.into()),
+          ("documentSymbolProvider", true.into()),
+          ("completionProvider", Json::object(vec![])),
+        ])),
+        ("serverInfo", Json::object(vec![("name", "cse262-lsp".into())])),
+      ])),
+      "shutdown" => {
+        self.shutdown = true;
+        Ok(Json::Null)
+      },
+      "textDocument/definition" => {
+        let (uri, document, offset) = self.at(params)?;
+        Ok(match document.analysis.definition(offset) {
+          Some(span) => Json::object(vec![("uri", uri.into()), ("range", range(&document.text, &span))]),
+          None => Json::Null,
+        })
+      },
+      "textDocument/hover" => {
+        let (_, document, offset) = self.at(params)?;
+        Ok(match document.analysis.hover(offset) {
+          Some((span, text)) => Json::object(vec![
+            ("contents", Json::object(vec![("kind", "plaintext".into()), ("value", text.into())])),
+            ("range", range(&document.text, &span)),
+          ]),
+          None => Json::Null,
+        })
+      },
+      "textDocument/documentSymbol" => {
+        let (_, document) = self.document(params)?;
+        Ok(Json::Array(document.analysis.symbols().iter().map(|symbol| Json::object(vec![
+          ("name", symbol.name.as_str().into()),
+          ("detail", symbol.signature.as_str().into()),
+          ("kind", SYMBOL_FUNCTION.into()),
+          ("range", range(&document.text, &symbol.span)),
+          ("selectionRange", range(&document.text, &symbol.name_span)),
+        ])).collect()))
+      },
+      "textDocument/completion" => {
+        let (_, document, offset) = self.at(params)?;
+        Ok(Json::Array(document.analysis.completions(offset).into_iter().map(|completion| {
+          let kind = match completion.kind {
+            CompletionKind::Function => COMPLETION_FUNCTION,
+            CompletionKind::Variable => COMPLETION_VARIABLE,
+          };
+          Json::object(vec![("label", completion.label.into()), ("kind", kind.into()), ("detail", completion.detail.into())])
+        }).collect()))
+      },
+      _ => Err((METHOD_NOT_FOUND, "Method not found")),
+    }
+  }
+
+  fn document<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a Document), Failure> {
+    let uri = params.get(&["textDocument", "uri"]).and_then(Json::as_str).ok_or((INVALID_PARAMS, "Missing document"))?;
+    let document = self.documents.get(uri).ok_or((INVALID_PARAMS, "Unknown document"))?;
+    Ok((uri, document))
+  }
+
+  // The document and offset a request is about
+  fn at<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a Document, usize), Failure> {
+    let (uri, document) = self.document(params)?;
+    let offset = params.get(&["position"]).and_then(|p| offset(&document.text, p)).ok_or((INVALID_PARAMS, "Bad position"))?;
+    Ok((uri, document, offset))
+  }
+
+  fn notification(&mut self, method: &str, params: &Json) {
+    let uri = params.get(&["textDocument", "uri"]).and_then(Json::as_str).unwrap_or_default().to_string();
+    match method {
+      "textDocument/didOpen" => {
+        let text = params.get(&["textDocument", "text"]).and_then(Json::as_str).unwrap_or_default();
+        self.open(uri, text.to_string());
+      },
+      // With full sync the last change is the whole document
+      "textDocument/didChange" => {
+        let text = match params.get(&["contentChanges"]) {
+          Some(Json::Array(changes)) => changes.last().and_then(|c| c.get(&["text"])).and_then(Json::as_str),
+          _ => None,
+        };
+        if let Some(text) = text {
+          self.open(uri, text.to_string());
+        }
+      },
+      "textDocument/didClose" => {
+        self.documents.remove(&uri);
+        publish(&uri, vec![]);
+      },
+      "exit" => process::exit(if self.shutdown { 0 } else { 1 }),
+      _ => (),
+    }
+  }
+
+  fn open(&mut self, uri: String, text: String) {
+    let analysis = Analysis::new(&text);
+    let diagnostics = analysis.diagnostics().iter().map(|d| {
+      let severity = match d.severity {
+        Severity::Error => SEVERITY_ERROR,
+        Severity::Warning => SEVERITY_WARNING,
+      };
+      Json::object(vec![
+        ("range", range(&text, &d.span)),
+        ("severity", severity.into()),
+        ("source", "cse262".into()),
+        ("message", d.message.as_str().into()),
+      ])
+    }).collect();
+    publish(&uri, diagnostics);
+    self.documents.insert(uri, Document{text, analysis});
+  }
+
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) {
+  write_message(Json::object(vec![
+    ("jsonrpc", "2.0".into()),
+    ("method", "textDocument/publishDiagnostics".into()),
+    ("params", Json::object(vec![("uri", uri.into()), ("diagnostics", Json::Array(diagnostics))])),
+  ]));
+}
+
+// Serves one client over stdin and stdout until it says to exit or the input ends
+fn main() {
+  let mut server = Server::default();
+  let stdin = io::stdin();
+  let mut input = stdin.lock();
+  while let Ok(Some(body)) = read_message(&mut input) {
+    match Json::parse(&body) {
+      Ok(message) => server.handle(&message),
+      Err(e) => write_message(Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", Json::Null),
+        ("error", Json::object(vec![("code", Json::Number(f64::from(PARSE_ERROR))), ("message", e.into())])),
+      ])),
+    }
+  }
+}
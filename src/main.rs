@@ -1,20 +1,187 @@
-extern crate nom;
 extern crate cse262_project;
 
-#[allow(unused_imports)]
-use cse262_project::{program, run, Node};
-
-fn main() -> Result<(), nom::Err<(&'static str, nom::error::ErrorKind)>> {
-  /*let (unparsed, ast) = program(r#"fn main(){return foo(1,2,3);} fn foo(a,b,c){return a+b+c;}"#)?;
-  println!("AST {:?}", ast);
-  let result = run(&ast);
-  println!("RESULT {:?}", result);*/
-  if  1+2==3  ||    !!false   {
-    let x = 5;
-    println!("Here");
+mod repl;
+
+use cse262_project::{parse_with_syntax, format_syntax, outline, to_json, check, compile, compile_with_lines, execute_with_globals, Bytecode, Interpreter, Node, Output, ParseError, RunLimits, Spans, SyntaxNode, TokenKind, Value, MAGIC};
+use std::collections::HashMap;
+use std::{env, fs, process};
+
+const USAGE: &str = "usage: cse262 run [--check] [--vm] [--emit out] file [args...]
+       cse262 ast [--json] file
+       cse262 fmt [--check] file...
+       cse262 repl";
+
+// Exit codes, following the BSD sysexits convention
+const EXIT_USAGE: i32 = 64;
+// The program doesn't parse, type check or load
+const EXIT_DATA: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+// The program stopped with a runtime error
+const EXIT_SOFTWARE: i32 = 70;
+const EXIT_IO: i32 = 74;
+
+fn fail(code: i32, message: String) -> ! {
+  eprintln!("{}", message);
+  process::exit(code);
+}
+
+fn main() {
+  let mut args = env::args().skip(1);
+  match args.next().as_deref() {
+    Some("run") => run_command(args),
+    Some("ast") => ast_command(args),
+    Some("fmt") => fmt_command(args),
+    Some("repl") => repl::repl(),
+    _ => fail(EXIT_USAGE, USAGE.to_string()),
+  }
+}
+
+// Line numbers start at 1, counted up to where rest begins in source
+fn line_of(source: &str, rest: &str) -> usize {
+  source[..source.len() - rest.len()].matches('\n').count() + 1
+}
+
+// Every syntax error is reported, each on its own line
+fn syntax_errors(source: &str, errors: &[ParseError]) -> Result<(), String> {
+  if errors.is_empty() {
+    return Ok(());
+  }
+  let lines: Vec<String> = errors.iter().map(|e| format!("parse error on line {}: {}", line_of(source, &source[e.span.start..]), e.message)).collect();
+  Err(lines.join("\n"))
+}
+
+fn parse(source: &str) -> Result<Node, String> {
+  let (ast, errors) = cse262_project::parse(source);
+  syntax_errors(source, &errors).map(|_| ast)
+}
+
+fn parse_tree(source: &str) -> Result<(Node, SyntaxNode), String> {
+  let (ast, tree, errors) = parse_with_syntax(source);
+  syntax_errors(source, &errors).map(|_| (ast, tree))
+}
+
+// Options come before the file and everything after it is passed to the script, which
+// sees it as the global `args`, a List of Strings read with `get(args, i)`. --check type
+// checks before running, --vm runs on the bytecode virtual machine instead of the tree
+// walker, and --emit writes the compiled program to a file instead of running it. A
+// compiled file runs on the virtual machine. The script's result is printed unless it is
+// nil.
+fn run_command(mut args: impl Iterator<Item = String>) {
+  let mut check_types = false;
+  let mut use_vm = false;
+  let mut emit = None;
+  let path = loop {
+    match args.next() {
+      Some(arg) => match arg.as_ref() {
+        "--check" => check_types = true,
+        "--vm" => use_vm = true,
+        "--emit" => emit = Some(args.next().unwrap_or_else(|| fail(EXIT_USAGE, "--emit needs an output file".to_string()))),
+        _ if arg.starts_with("--") => fail(EXIT_USAGE, format!("unknown option {}\n{}", arg, USAGE)),
+        _ => break arg,
+      },
+      None => fail(EXIT_USAGE, USAGE.to_string()),
+    }
+  };
+  let mut globals = HashMap::new();
+  globals.insert("args".to_string(), Value::List(args.map(Value::String).collect()));
+  let bytes = fs::read(&path).unwrap_or_else(|e| fail(EXIT_NO_INPUT, format!("cannot read {}: {}", path, e)));
+  let vm = |bytecode: &Bytecode, globals| execute_with_globals(bytecode, &RunLimits::default(), &Output::stdout(), globals);
+  let result = if bytes.starts_with(MAGIC) {
+    let bytecode = Bytecode::from_bytes(&bytes).unwrap_or_else(|e| fail(EXIT_DATA, format!("cannot load program: {}", e)));
+    vm(&bytecode, globals)
   } else {
-    println!("Not here");
+    let source = String::from_utf8(bytes).unwrap_or_else(|_| fail(EXIT_DATA, format!("{} is not UTF-8", path)));
+    let (ast, tree) = parse_tree(&source).unwrap_or_else(|e| fail(EXIT_DATA, e));
+    if check_types {
+      if let Err(errors) = check(&ast, &Spans::new(&ast, &tree)) {
+        for e in errors {
+          eprintln!("type error on line {} {}", line_of(&source, &source[e.span.start..]), e);
+        }
+        process::exit(EXIT_DATA);
+      }
+    }
+    if let Some(out) = emit {
+      let bytecode = compile_with_lines(&ast, &Spans::new(&ast, &tree), &source);
+      if let Err(e) = fs::write(&out, bytecode.to_bytes()) {
+        fail(EXIT_IO, format!("cannot write {}: {}", out, e));
+      }
+      return;
+    }
+    if use_vm {
+      vm(&compile(&ast), globals)
+    } else {
+      let mut interpreter = Interpreter::new();
+      for (name, value) in globals {
+        interpreter.set_global(&name, value);
+      }
+      interpreter.run(&ast)
+    }
+  };
+  match result {
+    Ok(Value::Nil) => (),
+    Ok(value) => println!("{}", value),
+    Err(e) => fail(EXIT_SOFTWARE, format!("runtime error: {}", e)),
   }
-  Ok(())
 }
 
+// Prints the parsed tree as an indented outline, or as JSON with --json
+fn ast_command(args: impl Iterator<Item = String>) {
+  let mut json = false;
+  let mut path = None;
+  for arg in args {
+    match arg.as_ref() {
+      "--json" => json = true,
+      _ if arg.starts_with("--") => fail(EXIT_USAGE, format!("unknown option {}\n{}", arg, USAGE)),
+      _ if path.is_none() => path = Some(arg),
+      _ => fail(EXIT_USAGE, USAGE.to_string()),
+    }
+  }
+  let path = path.unwrap_or_else(|| fail(EXIT_USAGE, USAGE.to_string()));
+  let source = fs::read_to_string(&path).unwrap_or_else(|e| fail(EXIT_NO_INPUT, format!("cannot read {}: {}", path, e)));
+  let ast = parse(&source).unwrap_or_else(|e| fail(EXIT_DATA, e));
+  print!("{}", if json { to_json(&ast) } else { outline(&ast) });
+}
+
+// Rewrites each file in the canonical layout, keeping its comments. With --check nothing
+// is written, and the exit status is 1 when any file isn't already formatted.
+fn fmt_command(args: impl Iterator<Item = String>) {
+  let mut check_only = false;
+  let mut paths = vec![];
+  for arg in args {
+    match arg.as_ref() {
+      "--check" => check_only = true,
+      _ if arg.starts_with("--") => fail(EXIT_USAGE, format!("unknown option {}\n{}", arg, USAGE)),
+      _ => paths.push(arg),
+    }
+  }
+  if paths.is_empty() {
+    fail(EXIT_USAGE, USAGE.to_string());
+  }
+  let mut unformatted = false;
+  for path in paths {
+    let source = fs::read_to_string(&path).unwrap_or_else(|e| fail(EXIT_NO_INPUT, format!("cannot read {}: {}", path, e)));
+    let (ast, tree) = parse_tree(&source).unwrap_or_else(|e| fail(EXIT_DATA, format!("{}: {}", path, e)));
+    let formatted = format_syntax(&ast, &tree);
+    if formatted == source {
+      continue;
+    }
+    // Never write a file that would mean something else or has lost a comment, as one
+    // inside an if used as a value would be
+    let comments = |tree: &SyntaxNode| tree.tokens().into_iter().filter(|token| token.kind == TokenKind::Comment).map(|token| token.text.clone()).collect::<Vec<_>>();
+    match parse_tree(&formatted) {
+      Ok((new_ast, _)) if new_ast != ast => fail(EXIT_SOFTWARE, format!("cannot format {}: the result parses differently", path)),
+      Ok((_, new_tree)) if comments(&new_tree) != comments(&tree) => fail(EXIT_SOFTWARE, format!("cannot format {}: comments would be lost", path)),
+      Ok(_) => (),
+      Err(_) => fail(EXIT_SOFTWARE, format!("cannot format {}: the result parses differently", path)),
+    }
+    if check_only {
+      println!("{} is not formatted", path);
+      unformatted = true;
+    } else if let Err(e) = fs::write(&path, formatted) {
+      fail(EXIT_IO, format!("cannot write {}: {}", path, e));
+    }
+  }
+  if unformatted {
+    process::exit(1);
+  }
+}
@@ -0,0 +1,329 @@
+use crate::parser::Node;
+use crate::runtime::{compare, has_type, is_return, math, Value};
+use std::collections::{HashMap, HashSet};
+
+// Rebuilds a node with f applied to each of its children
+fn map(node: &Node, f: &mut dyn FnMut(&Node) -> Node) -> Node {
+  let all = |nodes: &[Node], f: &mut dyn FnMut(&Node) -> Node| nodes.iter().map(&mut *f).collect::<Vec<Node>>();
+  match node {
+    Node::Program{children} => Node::Program{children: all(children, f)},
+    Node::Statement{children} => Node::Statement{children: all(children, f)},
+    Node::FunctionReturn{children} => Node::FunctionReturn{children: all(children, f)},
+    Node::FunctionDefine{children} => Node::FunctionDefine{children: all(children, f)},
+    Node::FunctionArguments{children} => Node::FunctionArguments{children: all(children, f)},
+    Node::FunctionStatements{children} => Node::FunctionStatements{children: all(children, f)},
+    Node::Expression{children} => Node::Expression{children: all(children, f)},
+    Node::VariableDefine{children} => Node::VariableDefine{children: all(children, f)},
+    Node::TestEquality{children} => Node::TestEquality{children: all(children, f)},
+    Node::Condition{conditions} => Node::Condition{conditions: all(conditions, f)},
+    Node::MathExpression{name, children} => Node::MathExpression{name: name.clone(), children: all(children, f)},
+    Node::FunctionCall{name, children} => Node::FunctionCall{name: name.clone(), children: all(children, f)},
+    Node::ConditionExpression{name, children} => Node::ConditionExpression{name: name.clone(), children: all(children, f)},
+    Node::If{condition, children} => Node::If{condition: all(condition, f), children: all(children, f)},
+    Node::While{condition, children} => Node::While{condition: all(condition, f), children: all(children, f)},
+    _ => node.clone(),
+  }
+}
+
+fn literal(node: &Node) -> Option<Value> {
+  match node {
+    Node::Number{value} => Some(Value::Number(*value)),
+    Node::Bool{value} => Some(Value::Bool(*value)),
+    Node::String{value} => Some(Value::String(value.clone())),
+    Node::Nil => Some(Value::Nil),
+    Node::Condition{conditions} => literal(&conditions[0]),
+    _ => None,
+  }
+}
+
+fn boolean(node: &Node) -> Option<bool> {
+  match literal(node) {
+    Some(Value::Bool(value)) => Some(value),
+    _ => None,
+  }
+}
+
+// Only operations that can't fail are folded, with the runtime's own arithmetic.
+// Overflow, division by zero and math on the wrong types are left in place so they still
+// fail at runtime exactly as before.
+fn fold_math(op: &str, lhs: &Node, rhs: &Node) -> Option<Node> {
+  match math(op, literal(lhs)?, literal(rhs)?) {
+    Ok(Value::Number(value)) => Some(Node::Number{value}),
+    _ => None,
+  }
+}
+
+fn fold_condition(op: &str, children: &[Node]) -> Option<Node> {
+  if op == "!" {
+    return Some(Node::Bool{value: !boolean(&children[0])?});
+  }
+  let lhs = literal(&children[0])?;
+  let value = match (op, &lhs) {
+    // The right side of a short circuit is never run, so it can be anything
+    ("&&", Value::Bool(false)) | ("&", Value::Bool(false)) => false,
+    ("||", Value::Bool(true)) | ("|", Value::Bool(true)) => true,
+    ("&&", Value::Bool(true)) | ("&", Value::Bool(true)) |
+    ("||", Value::Bool(false)) | ("|", Value::Bool(false)) => boolean(&children[1])?,
+    ("!=", _) => lhs != literal(&children[1])?,
+    ("<", Value::Number(_)) | (">", Value::Number(_)) | ("<=", Value::Number(_)) | (">=", Value::Number(_)) => {
+      match compare(op, lhs, literal(&children[1])?) {
+        Ok(Value::Bool(value)) => value,
+        _ => return None,
+      }
+    },
+    _ => return None,
+  };
+  Some(Node::Bool{value})
+}
+
+// Replaces math and conditions on literals with their result
+pub fn fold_constants(node: &Node) -> Node {
+  let node = map(node, &mut fold_constants);
+  let folded = match &node {
+    Node::MathExpression{name, children} => fold_math(name, &children[0], &children[1]),
+    Node::ConditionExpression{name, children} => fold_condition(name, children),
+    Node::TestEquality{children} => {
+      match (literal(&children[0]), literal(&children[1])) {
+        (Some(lhs), Some(rhs)) => Some(Node::Bool{value: lhs == rhs}),
+        _ => None,
+      }
+    },
+    _ => None,
+  };
+  folded.unwrap_or(node)
+}
+
+// Drops if branches and loops whose condition is a constant false, and replaces an if
+// whose first remaining condition is a constant true with that branch's statements, then
+// drops anything after a return.
+// Variables are function scoped, so lifting statements out of a block changes nothing.
+pub fn eliminate_dead_branches(node: &Node) -> Node {
+  let node = map(node, &mut eliminate_dead_branches);
+  // Statements after a return never run
+  let prune = |statements: &[Node]| -> Vec<Node> {
+    let mut kept: Vec<Node> = vec![];
+    for n in statements.iter().flat_map(prune_statement) {
+      let done = is_return(&n);
+      kept.push(n);
+      if done {
+        break;
+      }
+    }
+    kept
+  };
+  match &node {
+    Node::FunctionDefine{children} => Node::FunctionDefine{children: prune(children)},
+    Node::FunctionStatements{children} => Node::FunctionStatements{children: prune(children)},
+    Node::While{condition, children} => Node::While{condition: condition.clone(), children: prune(children)},
+    _ => node,
+  }
+}
+
+fn prune_statement(node: &Node) -> Vec<Node> {
+  match node {
+    Node::If{condition, children} => {
+      let mut kept_conditions = vec![];
+      let mut kept_bodies = vec![];
+      let mut otherwise = children.get(condition.len()).cloned();
+      for (c, body) in condition.iter().zip(children) {
+        match boolean(c) {
+          Some(false) => (),
+          Some(true) => {
+            otherwise = Some(body.clone());
+            break;
+          },
+          None => {
+            kept_conditions.push(c.clone());
+            kept_bodies.push(body.clone());
+          },
+        }
+      }
+      if kept_conditions.is_empty() {
+        match otherwise {
+          Some(Node::FunctionStatements{children}) => children,
+          _ => vec![],
+        }
+      } else {
+        kept_bodies.extend(otherwise);
+        vec![Node::If{condition: kept_conditions, children: kept_bodies}]
+      }
+    },
+    Node::While{condition, ..} if boolean(&condition[0]) == Some(false) => vec![],
+    _ => vec![node.clone()],
+  }
+}
+
+fn reads(node: &Node, names: &mut HashSet<String>) {
+  match node {
+    Node::Identifier{value} |
+    Node::Local{value, ..} => {
+      names.insert(value.clone());
+    },
+    // The variable being defined isn't a read
+    Node::VariableDefine{children} => {
+      for child in &children[1..] {
+        reads(child, names);
+      }
+    },
+    _ => {
+      map(node, &mut |child| {
+        reads(child, names);
+        child.clone()
+      });
+    },
+  }
+}
+
+// A let whose variable is never read and whose value is a literal that satisfies its
+// annotation can't affect the result, so it is removed. Lets with other values are kept
+// because evaluating them may fail. Top level statements are kept since the last one is
+// the program's result.
+pub fn remove_unused_lets(node: &Node) -> Node {
+  match node {
+    Node::Program{children} => Node::Program{children: children.iter().map(remove_unused_lets).collect()},
+    Node::FunctionDefine{..} => {
+      let mut used = HashSet::new();
+      reads(node, &mut used);
+      remove_lets(node, &used)
+    },
+    _ => node.clone(),
+  }
+}
+
+fn remove_lets(node: &Node, used: &HashSet<String>) -> Node {
+  let node = map(node, &mut |child| remove_lets(child, used));
+  let keep = |n: &Node| -> bool {
+    let children = match n {
+      Node::Statement{children} => match children.first() {
+        Some(Node::VariableDefine{children}) => children,
+        _ => return true,
+      },
+      _ => return true,
+    };
+    let name = match &children[0] {
+      Node::Identifier{value} | Node::Local{value, ..} => value,
+      _ => return true,
+    };
+    let value = match &children[1] {
+      Node::Expression{children} => literal(&children[0]),
+      other => literal(other),
+    };
+    let fits = match (&value, children.get(2)) {
+      (Some(value), Some(Node::TypeAnnotation{value: annotation})) => has_type(value, annotation),
+      (Some(_), _) => true,
+      (None, _) => false,
+    };
+    used.contains(name) || !fits
+  };
+  match &node {
+    Node::FunctionDefine{children} => Node::FunctionDefine{children: children.iter().filter(|n| keep(n)).cloned().collect()},
+    Node::FunctionStatements{children} => Node::FunctionStatements{children: children.iter().filter(|n| keep(n)).cloned().collect()},
+    Node::While{condition, children} => Node::While{condition: condition.clone(), children: children.iter().filter(|n| keep(n)).cloned().collect()},
+    _ => node,
+  }
+}
+
+// A trivial function has no annotations and a body of just `return E;` where E uses only
+// its parameters and literals
+struct Trivial {
+  params: Vec<String>,
+  body: Node,
+}
+
+fn trivial(children: &[Node]) -> Option<Trivial> {
+  let mut params = vec![];
+  let mut rest = &children[1..];
+  if let Some(Node::FunctionArguments{children}) = rest.first() {
+    for param in children {
+      match param {
+        Node::Expression{children} if children.len() == 1 => match &children[0] {
+          Node::Identifier{value} | Node::Local{value, ..} => params.push(value.clone()),
+          _ => return None,
+        },
+        _ => return None,
+      }
+    }
+    rest = &rest[1..];
+  }
+  let body = match rest {
+    [Node::Statement{children}] => match children.as_slice() {
+      [Node::FunctionReturn{children}] if children.len() == 1 => children[0].clone(),
+      _ => return None,
+    },
+    _ => return None,
+  };
+  fn simple(node: &Node, params: &[String]) -> bool {
+    match node {
+      Node::Identifier{value} | Node::Local{value, ..} => params.contains(value),
+      Node::Number{..} | Node::Bool{..} | Node::String{..} | Node::Nil => true,
+      Node::Expression{children} | Node::MathExpression{children, ..} => children.iter().all(|c| simple(c, params)),
+      _ => false,
+    }
+  }
+  if simple(&body, &params) {
+    Some(Trivial{params, body})
+  } else {
+    None
+  }
+}
+
+fn substitute(node: &Node, bindings: &HashMap<String, Node>) -> Node {
+  match node {
+    Node::Identifier{value} | Node::Local{value, ..} => bindings[value].clone(),
+    _ => map(node, &mut |child| substitute(child, bindings)),
+  }
+}
+
+// Replaces calls to trivial functions with the function's expression when every argument
+// is a literal, so nothing is evaluated in a different order or a different number of times
+pub fn inline_trivial_functions(node: &Node) -> Node {
+  let children = match node {
+    Node::Program{children} => children,
+    _ => return node.clone(),
+  };
+  // As in the runtime, a later definition replaces an earlier one
+  let mut functions = HashMap::new();
+  for n in children {
+    if let Node::FunctionDefine{children} = n {
+      if let Some(Node::Identifier{value}) = children.first() {
+        functions.insert(value.clone(), trivial(children));
+      }
+    }
+  }
+  inline(node, &functions)
+}
+
+fn inline(node: &Node, functions: &HashMap<String, Option<Trivial>>) -> Node {
+  let node = map(node, &mut |child| inline(child, functions));
+  if let Node::FunctionCall{name, children} = &node {
+    if let Some(Some(function)) = functions.get(name) {
+      let in_args = match children.first() {
+        Some(Node::FunctionArguments{children}) => children.as_slice(),
+        _ => children.as_slice(),
+      };
+      let args: Option<Vec<Node>> = in_args.iter().map(|arg| match arg {
+        Node::Expression{children} if literal(&children[0]).is_some() => Some(children[0].clone()),
+        _ if literal(arg).is_some() => Some(arg.clone()),
+        _ => None,
+      }).collect();
+      if let Some(args) = args {
+        if args.len() == function.params.len() {
+          let bindings = function.params.iter().cloned().zip(args).collect();
+          return match substitute(&function.body, &bindings) {
+            Node::Expression{children} => children[0].clone(),
+            body => body,
+          };
+        }
+      }
+    }
+  }
+  node
+}
+
+// Runs every pass. Inlining goes first so the inlined expressions get folded.
+pub fn optimize(node: &Node) -> Node {
+  let node = inline_trivial_functions(node);
+  let node = fold_constants(&node);
+  let node = eliminate_dead_branches(&node);
+  remove_unused_lets(&node)
+}
@@ -0,0 +1,64 @@
+use crate::runtime::RuntimeError;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Where print and println write. Stdout unless the host plugs in another writer, such as an
+// OutputBuffer to read back what a script printed. Clones write to the same sink.
+#[derive(Clone)]
+pub struct Output {
+  sink: Rc<RefCell<dyn Write>>,
+}
+
+impl Default for Output {
+  fn default() -> Output {
+    Output::stdout()
+  }
+}
+
+impl Output {
+
+  pub fn stdout() -> Output {
+    Output::new(io::stdout())
+  }
+
+  pub fn new<W: Write + 'static>(writer: W) -> Output {
+    Output{sink: Rc::new(RefCell::new(writer))}
+  }
+
+  // Flushes after every write, so a script's output appears in order with the host's own
+  pub(crate) fn write(&self, text: &str) -> Result<(), RuntimeError> {
+    let mut sink = self.sink.borrow_mut();
+    sink.write_all(text.as_bytes()).and_then(|_| sink.flush()).map_err(|_| "Cannot write output")
+  }
+
+}
+
+// Collects output in memory. Clones share the same text, so keep one to read it back.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer {
+  bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl OutputBuffer {
+
+  pub fn new() -> OutputBuffer {
+    OutputBuffer::default()
+  }
+
+  pub fn contents(&self) -> String {
+    String::from_utf8_lossy(&self.bytes.borrow()).into_owned()
+  }
+
+}
+
+impl Write for OutputBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.bytes.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
@@ -5,13 +5,15 @@ use nom::{
   IResult,
   branch::alt,
   combinator::opt,
+  error::ErrorKind,
   multi::{many1, many0},
-  bytes::complete::{tag},
-  character::complete::{alphanumeric1, digit1},
 };
+use crate::lexer::{number_value, tokenize, TYPE_NAMES};
+use crate::syntax::{Syntax, SyntaxKind, SyntaxNode, Token, TokenKind};
+use std::ops::Range;
 // Here are the different node types. You will use these to make your parser and your grammar.
 // You may add other nodes as you see fit, but these are expected by the runtime.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Node {
   Program { children: Vec<Node> },
   Statement { children: Vec<Node> },
@@ -26,330 +28,517 @@ pub enum Node {
   Number { value: i32 },
   Bool { value: bool },
   Identifier { value: String },
+  // An identifier bound by the resolver to a slot in its function's frame
+  Local { value: String, slot: usize },
   String { value: String },
+  Nil,
+  TypeAnnotation { value: String },
   If {condition: Vec<Node>, children: Vec<Node>},
+  While {condition: Vec<Node>, children: Vec<Node>},
   Condition {conditions: Vec<Node>},
   ConditionExpression{name: String, children: Vec<Node>},
   TestEquality {children: Vec<Node>}
 }
-// Define production rules for an identifier
-pub fn identifier(input: &str) -> IResult<&str, Node> {
-  let (input, result) = alphanumeric1(input)?;              // Consume at least 1 alphanumeric character. The ? automatically unwraps the result if it's okay and bails if it is an error.
-  Ok((input, Node::Identifier{ value: result.to_string()})) // Return the now partially consumed input, as well as a node with the string on it.
-}
-
-/*pub fn n(input: &str) -> IResult<&str, Node> {
-  let (input, result) = alt((tag("0"),tag("1"),tag("2"),tag("3"),tag("4"),tag("5"),tag("6"),tag("7"),tag("8"),tag("9")))(input)?;
-}*/
-
-pub fn binary(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("0")(input)?;
-  let (input, _) = tag("b")(input)?;
-  let (input, result) = many0(alt((tag("0"), tag("1"))))(input)?;
-  let mut s = String::from("");
-  for i in result {
-    s += i;
+// The parser reads the tokens the lexer made, with the trivia left out
+pub type Tokens<'a> = &'a [Token];
+
+// What a rule read: its node and the syntax it was read from
+pub type Parsed = (Node, Syntax);
+
+// One token of a kind with the given text
+fn token<'a>(kind: TokenKind, text: &'static str) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, &'a Token> {
+  move |input: Tokens<'a>| match input.split_first() {
+    Some((first, rest)) if first.kind == kind && first.text == text => Ok((rest, first)),
+    _ => Err(nom::Err::Error((input, ErrorKind::Tag))),
   }
-  let intval = i32::from_str_radix(&s, 2).unwrap();
-  Ok((input, Node::Number{value: intval}))
 }
 
-pub fn octal(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("0")(input)?;
-  let (input, _) = tag("o")(input)?;
-  let (input, result) = many0(alt((tag("0"), tag("1"), tag("2"), tag("3"), tag("4"), tag("5"), tag("6"), tag("7"), tag("8"))))(input)?;
-  let mut s = String::from("");
-  for i in result {
-    s += i;
+// One token of a kind, whatever its text
+fn any<'a>(kind: TokenKind) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, &'a Token> {
+  move |input: Tokens<'a>| match input.split_first() {
+    Some((first, rest)) if first.kind == kind => Ok((rest, first)),
+    _ => Err(nom::Err::Error((input, ErrorKind::Tag))),
   }
-  let intval = i32::from_str_radix(&s, 8).unwrap();
-  Ok((input, Node::Number{value: intval}))
 }
 
-pub fn decimal(input: &str) -> IResult<&str, Node> {
-  unimplemented!();
+fn keyword<'a>(word: &'static str) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, &'a Token> {
+  token(TokenKind::Keyword, word)
 }
 
-pub fn hexidecimal(input: &str) -> IResult<&str, Node> {
-  unimplemented!();
+fn punctuation<'a>(text: &'static str) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, &'a Token> {
+  token(TokenKind::Punctuation, text)
 }
 
-pub fn scientific(input: &str) -> IResult<&str, Node> {
-  unimplemented!();
+fn operator<'a>(text: &'static str) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, &'a Token> {
+  token(TokenKind::Operator, text)
 }
 
-pub fn integer(input: &str) -> IResult<&str, Node> {
-  alt((integer0, integer1))(input)
+// Each operation after the first operand takes everything before it as its left side
+fn fold(head: Parsed, tail: Vec<(String, Parsed)>, kind: SyntaxKind) -> Parsed {
+  tail.into_iter().fold(head, |(lhs, syntax), (name, (rhs, rest))| {
+    let children = vec![lhs, rhs];
+    let node = match kind {
+      SyntaxKind::MathExpression => Node::MathExpression{name, children},
+      _ => Node::ConditionExpression{name, children},
+    };
+    (node, syntax.then(rest).node(kind))
+  })
 }
 
-pub fn integer0(input: &str) -> IResult<&str, Node> {
-  let (input, result) = tag("0")(input)?; 
-  let number = result.parse::<i32>().unwrap();
-  Ok((input, Node::Number{value: number}))
+// Define production rules for an identifier
+pub fn identifier(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = any(TokenKind::Identifier)(input)?;
+  Ok((input, (Node::Identifier{ value: result.text.clone()}, Syntax::from(result).node(SyntaxKind::Identifier))))
+}
+// The name a let, function or parameter introduces. A keyword there can only be a mistake,
+// so it fails the whole parse at the keyword rather than letting another rule try.
+pub fn new_name(input: Tokens) -> IResult<Tokens, Parsed> {
+  if any(TokenKind::Keyword)(input).is_ok() {
+    return Err(nom::Err::Failure((input, ErrorKind::Verify)));
+  }
+  identifier(input)
+}
+// The lexer only makes number tokens that have a value
+pub fn number(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = any(TokenKind::Number)(input)?;
+  Ok((input, (Node::Number{value: number_value(&result.text).unwrap()}, Syntax::from(result).node(SyntaxKind::Number))))
 }
 
-pub fn integer1(input: &str) -> IResult<&str, Node> {
-  let (input, r1) = alt((tag("1"),tag("2"),tag("3"),tag("4"),tag("5"),tag("6"),tag("7"),tag("8"),tag("9")))(input)?;
-  let (input, r2) = many0(digit1)(input)?;
-  let mut result = String::from(r1);
-  for i in r2 {
-    result += i;
-  }
-  let number = result.parse::<i32>().unwrap();
-  Ok((input, Node::Number{value: number}))
-}
-
-pub fn floating_point(input: &str) -> IResult<&str, Node> {
-  unimplemented!();
-}
-
-// Define an integer number
-pub fn number(input: &str) -> IResult<&str, Node> {
-  alt((binary, octal, integer))(input)
-  //alt((binary, octal, decimal, hexidecimal, scientific, integer, floating_point))(input)
-  /*let (input, result) = digit1(input)?;                     // Consume at least 1 digit 0-9
-  let number = result.parse::<i32>().unwrap();              // Parse the string result into a usize
-  Ok((input, Node::Number{ value: number}))*/                 // Return the now partially consumed input with a number as well
-}
-pub fn boolean(input: &str) -> IResult<&str, Node> {
-  let (input, result) = alt((tag("true"),tag("false")))(input)?;
-  let bool_value = if result == "true" {true} else {false};
-  Ok((input, Node::Bool{ value: bool_value}))
-}
-pub fn string(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("\"")(input)?;
-  let (input, string) = many1(alt((alphanumeric1,tag(" "))))(input)?;
-  let (input, _) = tag("\"")(input)?;
-  Ok((input, Node::String{ value: string.join("")}))
-}
-pub fn function_call(input: &str) -> IResult<&str, Node> {
-  let (input, name) = alphanumeric1(input)?;
-  let (input, _) = tag("(")(input)?;
-  let (input, mut args) = many0(arguments)(input)?;
-  let (input, _) = tag(")")(input)?;
-  Ok((input, Node::FunctionCall{name: name.to_string(), children: args}))   
-}
-pub fn parenthetical_expression(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag("(")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, args) = l1(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag(")")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  Ok((input, args))
-}
-pub fn parenthetical_condition(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag("(")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, args) = condition(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag(")")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  Ok((input, args))
-}
-
-pub fn l4(input: &str) -> IResult<&str, Node> {
-  alt((function_call, number, identifier, parenthetical_expression))(input)
-}
-pub fn l3_infix(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, op) = tag("^")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, args) = l4(input)?;
-  Ok((input, Node::MathExpression{name: op.to_string(), children: vec![args]}))
-}
-pub fn l3(input: &str) -> IResult<&str, Node> {
-  let (input, mut head) = l4(input)?;
+pub fn boolean(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = alt((keyword("true"), keyword("false")))(input)?;
+  Ok((input, (Node::Bool{ value: result.text == "true"}, Syntax::from(result).node(SyntaxKind::Bool))))
+}
+pub fn nil(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = keyword("nil")(input)?;
+  Ok((input, (Node::Nil, Syntax::from(result).node(SyntaxKind::Nil))))
+}
+pub fn string(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = any(TokenKind::String)(input)?;
+  let value = result.text[1..result.text.len() - 1].to_string();
+  Ok((input, (Node::String{ value }, Syntax::from(result).node(SyntaxKind::String))))
+}
+pub fn function_call(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, name) = any(TokenKind::Identifier)(input)?;
+  let (input, open) = punctuation("(")(input)?;
+  let (input, args) = opt(arguments)(input)?;
+  let (input, close) = punctuation(")")(input)?;
+  let (children, args): (Vec<Node>, Syntax) = args.into_iter().unzip();
+  let syntax = Syntax::from(name).then(open).then(args).then(close);
+  Ok((input, (Node::FunctionCall{name: name.text.clone(), children}, syntax.node(SyntaxKind::FunctionCall))))
+}
+// The parentheses don't make a node, so they go to the node around what they group
+pub fn parenthetical_expression(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, open) = punctuation("(")(input)?;
+  let (input, (args, syntax)) = l1(input)?;
+  let (input, close) = punctuation(")")(input)?;
+  Ok((input, (args, Syntax::from(open).then(syntax).then(close))))
+}
+// A condition inside another one is only there because of its parentheses, so they are in it
+pub fn parenthetical_condition(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, open) = punctuation("(")(input)?;
+  let (input, (head, syntax)) = condition_or(input)?;
+  let (input, close) = punctuation(")")(input)?;
+  let syntax = Syntax::from(open).then(syntax).then(close);
+  Ok((input, (Node::Condition{conditions: vec![head]}, syntax.node(SyntaxKind::Condition))))
+}
+
+// Any literal can be an operand so that mismatched math parses and is reported by the checker or runtime
+pub fn l4(input: Tokens) -> IResult<Tokens, Parsed> {
+  alt((function_call, number, boolean, nil, string, identifier, parenthetical_expression))(input)
+}
+pub fn l3_infix(input: Tokens) -> IResult<Tokens, (String, Parsed)> {
+  let (input, op) = operator("^")(input)?;
+  let (input, (args, syntax)) = l4(input)?;
+  Ok((input, (op.text.clone(), (args, Syntax::from(op).then(syntax)))))
+}
+pub fn l3(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, head) = l4(input)?;
   let (input, tail) = many0(l3_infix)(input)?;
-  for n in tail {
-    match n {
-      Node::MathExpression{name, mut children} => {
-        let mut new_children = vec![head.clone()];
-        new_children.append(&mut children);
-        head = Node::MathExpression{name, children: new_children};
-      }
-      _ => () 
-    };
-  }
-  Ok((input, head))
+  Ok((input, fold(head, tail, SyntaxKind::MathExpression)))
 }
-pub fn l2_infix(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, op) = alt((tag("*"),tag("/")))(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, args) = l2(input)?;
-  Ok((input, Node::MathExpression{name: op.to_string(), children: vec![args]}))
+pub fn l2_infix(input: Tokens) -> IResult<Tokens, (String, Parsed)> {
+  let (input, op) = alt((operator("*"),operator("/")))(input)?;
+  let (input, (args, syntax)) = l2(input)?;
+  Ok((input, (op.text.clone(), (args, Syntax::from(op).then(syntax)))))
 }
-pub fn l2(input: &str) -> IResult<&str, Node> {
-  let (input, mut head) = l3(input)?;
+pub fn l2(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, head) = l3(input)?;
   let (input, tail) = many0(l2_infix)(input)?;
-  for n in tail {
-    match n {
-      Node::MathExpression{name, mut children} => {
-        let mut new_children = vec![head.clone()];
-        new_children.append(&mut children);
-        head = Node::MathExpression{name, children: new_children};
-      }
-      _ => () 
-    };
-  }
-  Ok((input, head))
+  Ok((input, fold(head, tail, SyntaxKind::MathExpression)))
 }
-pub fn l1_infix(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, op) = alt((tag("+"),tag("-")))(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, args) = l2(input)?;
-  Ok((input, Node::MathExpression{name: op.to_string(), children: vec![args]}))
+pub fn l1_infix(input: Tokens) -> IResult<Tokens, (String, Parsed)> {
+  let (input, op) = alt((operator("+"),operator("-")))(input)?;
+  let (input, (args, syntax)) = l2(input)?;
+  Ok((input, (op.text.clone(), (args, Syntax::from(op).then(syntax)))))
 }
-pub fn l1(input: &str) -> IResult<&str, Node> {
-  let (input, mut head) = l2(input)?;
+pub fn l1(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, head) = l2(input)?;
   let (input, tail) = many0(l1_infix)(input)?;
-  for n in tail {
-    match n {
-      Node::MathExpression{name, mut children} => {
-        let mut new_children = vec![head.clone()];
-        new_children.append(&mut children);
-        head = Node::MathExpression{name, children: new_children};
-      }
-      _ => () 
-    };
-  }
-  Ok((input, head))
+  Ok((input, fold(head, tail, SyntaxKind::MathExpression)))
 }
-pub fn math_expression(input: &str) -> IResult<&str, Node> {
+pub fn math_expression(input: Tokens) -> IResult<Tokens, Parsed> {
   l1(input)
 }
 
-pub fn equality_math(input: &str) -> IResult<&str, Node> {
-  let (input, side1) = alt((boolean, math_expression))(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag("==")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, side2) = alt((boolean, math_expression))(input)?;
-  Ok((input, Node::TestEquality{children: vec![side1,side2]}))
+pub fn equality_math(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (side1, syntax1)) = alt((boolean, math_expression))(input)?;
+  let (input, op) = alt((operator("=="),operator("!="),operator("<="),operator(">="),operator("<"),operator(">")))(input)?;
+  let (input, (side2, syntax2)) = alt((boolean, math_expression))(input)?;
+  let syntax = syntax1.then(op).then(syntax2);
+  match op.text.as_str() {
+    "==" => Ok((input, (Node::TestEquality{children: vec![side1,side2]}, syntax.node(SyntaxKind::TestEquality)))),
+    _ => Ok((input, (Node::ConditionExpression{name: op.text.clone(), children: vec![side1,side2]}, syntax.node(SyntaxKind::ConditionExpression)))),
+  }
 }
 
-pub fn condition(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, not) = many0(tag("!"))(input)?;
-  let (input, c1) = alt((parenthetical_condition, equality_math, boolean, function_call))(input)?;
-  let (input, c2) = many0(condition_end)(input)?;
-  Ok((input, Node::Condition{conditions: vec![c1]}))
+pub fn condition(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (head, syntax)) = condition_or(input)?;
+  Ok((input, (Node::Condition{conditions: vec![head]}, syntax.node(SyntaxKind::Condition))))
 }
 
-pub fn condition_end(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, op) = alt((tag("&&"),tag("&"),tag("||"),tag("|")))(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, c2) = condition(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  Ok((input, Node::ConditionExpression{name: String::from(op), children: vec![c2]}))
+// A condition is a chain of && terms joined by ||, so && binds tighter
+pub fn condition_or(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, head) = condition_and(input)?;
+  let (input, tail) = many0(condition_end)(input)?;
+  Ok((input, fold(head, tail, SyntaxKind::ConditionExpression)))
 }
 
-pub fn body(input: &str) -> IResult<&str, Vec<Node>> {
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag("{")(input)?;
-  let (input, _) = many0(alt((tag(" "),tag("\t"),tag("\n"))))(input)?;
-  let (input, e) = many1(alt((statement, expression)))(input)?; // change this to be many statement/expression
-  let (input, _) = many0(alt((tag(" "),tag("\t"),tag("\n"))))(input)?;
-  let (input, _) = tag("}")(input)?;
-  Ok((input, e))
+pub fn condition_end(input: Tokens) -> IResult<Tokens, (String, Parsed)> {
+  let (input, op) = alt((operator("||"),operator("|")))(input)?;
+  let (input, (c2, syntax)) = condition_and(input)?;
+  Ok((input, (op.text.clone(), (c2, Syntax::from(op).then(syntax)))))
 }
 
-pub fn condition_body(input: &str) -> IResult<&str, Node> {
-  let (input, c) = condition(input)?;
-  let (input, if_stmt) = body(input)?;
-  let (input, _) = many0(alt((tag(" "),tag("\t"),tag("\n"))))(input)?;
-  Ok((input, Node::If{condition: vec![], children: vec![]}))
+pub fn condition_and(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, head) = condition_not(input)?;
+  let (input, tail) = many0(condition_and_end)(input)?;
+  Ok((input, fold(head, tail, SyntaxKind::ConditionExpression)))
 }
 
-pub fn elif(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("else if ")(input)?;
-  let (input, c) = condition_body(input)?;
-  Ok((input, Node::If{condition: vec![], children: vec![]}))
+pub fn condition_and_end(input: Tokens) -> IResult<Tokens, (String, Parsed)> {
+  let (input, op) = alt((operator("&&"),operator("&")))(input)?;
+  let (input, (c2, syntax)) = condition_not(input)?;
+  Ok((input, (op.text.clone(), (c2, Syntax::from(op).then(syntax)))))
+}
+
+// Each leading ! wraps the term in another negation, the last one innermost
+pub fn condition_not(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, nots) = many0(operator("!"))(input)?;
+  let (input, (mut result, mut syntax)) = alt((equality_math, parenthetical_condition, boolean, math_expression))(input)?;
+  for not in nots.into_iter().rev() {
+    result = Node::ConditionExpression{name: String::from("!"), children: vec![result]};
+    syntax = Syntax::from(not).then(syntax).node(SyntaxKind::ConditionExpression);
+  }
+  Ok((input, (result, syntax)))
 }
 
-pub fn else_stmt(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("else ")(input)?;
-  let (input, if_stmt) = body(input)?;
-  let (input, _) = many0(alt((tag(" "),tag("\t"),tag("\n"))))(input)?;
-  Ok((input, Node::If{condition: vec![], children: vec![]}))
+pub fn block_statement(input: Tokens) -> IResult<Tokens, Parsed> {
+  alt((statement, if_stmt, while_stmt))(input)
 }
 
-pub fn if_stmt(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(alt((tag(" "),tag("\t"), tag("\n"))))(input)?;
-  let (input, _) = tag("if ")(input)?;
-  let (input, c) = condition_body(input)?;
-  let (input, elif) = many0(elif)(input)?;
-  let (input, else_stmt) = opt(else_stmt)(input)?;
-  Ok((input, Node::If{condition: vec![], children: vec![]}))
+// The statements in braces, with the braces in the syntax
+pub fn body(input: Tokens) -> IResult<Tokens, (Vec<Node>, Syntax)> {
+  let (input, open) = punctuation("{")(input)?;
+  let (input, e) = many0(alt((block_statement, expression)))(input)?;
+  let (input, close) = punctuation("}")(input)?;
+  let (statements, syntax): (Vec<Node>, Syntax) = e.into_iter().unzip();
+  Ok((input, (statements, Syntax::from(open).then(syntax).then(close))))
 }
 
+pub fn condition_body(input: Tokens) -> IResult<Tokens, ((Node, Node), Syntax)> {
+  let (input, (c, condition)) = condition(input)?;
+  let (input, (statements, syntax)) = body(input)?;
+  let syntax = condition.then(syntax.node(SyntaxKind::FunctionStatements));
+  Ok((input, ((c, Node::FunctionStatements{children: statements}), syntax)))
+}
 
+pub fn elif(input: Tokens) -> IResult<Tokens, ((Node, Node), Syntax)> {
+  let (input, else_keyword) = keyword("else")(input)?;
+  let (input, if_keyword) = keyword("if")(input)?;
+  let (input, (branch, syntax)) = condition_body(input)?;
+  Ok((input, (branch, Syntax::from(else_keyword).then(if_keyword).then(syntax))))
+}
 
-pub fn expression(input: &str) -> IResult<&str, Node> {
-  let (input, result) = alt((boolean, if_stmt, math_expression, function_call, number, string, identifier))(input)?;
-  Ok((input, Node::Expression{ children: vec![result]}))   
+pub fn else_stmt(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, else_keyword) = keyword("else")(input)?;
+  let (input, (statements, syntax)) = body(input)?;
+  let syntax = Syntax::from(else_keyword).then(syntax.node(SyntaxKind::FunctionStatements));
+  Ok((input, (Node::FunctionStatements{children: statements}, syntax)))
 }
 
-pub fn statement(input: &str) -> IResult<&str, Node> {
-  let (input, _) = many0(alt((tag(" "),tag("\t"), tag("\n"), tag(" "))))(input)?;
-  let (input, result) = alt((variable_define, function_return))(input)?;
-  let (input, _) = tag(";")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = many0(tag("\n"))(input)?;
-  Ok((input, Node::Statement{ children: vec![result]}))   
+// An if chain holds one condition per branch and one body per branch, in order.
+// A trailing else adds a body without a matching condition.
+pub fn if_stmt(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, if_keyword) = keyword("if")(input)?;
+  let (input, first) = condition_body(input)?;
+  let (input, elifs) = many0(elif)(input)?;
+  let (input, else_body) = opt(else_stmt)(input)?;
+  let mut condition = vec![];
+  let mut children = vec![];
+  let mut syntax = Syntax::from(if_keyword);
+  for ((c, statements), branch) in std::iter::once(first).chain(elifs) {
+    condition.push(c);
+    children.push(statements);
+    syntax = syntax.then(branch);
+  }
+  if let Some((statements, branch)) = else_body {
+    children.push(statements);
+    syntax = syntax.then(branch);
+  }
+  Ok((input, (Node::If{condition, children}, syntax.node(SyntaxKind::If))))
 }
-pub fn function_return(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("return ")(input)?;
-  let (input, return_value) = alt((function_call, expression, identifier))(input)?;
-  Ok((input, Node::FunctionReturn{ children: vec![return_value]}))
+
+pub fn while_stmt(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, while_keyword) = keyword("while")(input)?;
+  let (input, (c, condition)) = condition(input)?;
+  let (input, (statements, syntax)) = body(input)?;
+  let syntax = Syntax::from(while_keyword).then(condition).then(syntax);
+  Ok((input, (Node::While{condition: vec![c], children: statements}, syntax.node(SyntaxKind::While))))
 }
-pub fn variable_define(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("let ")(input)?;
-  let (input, variable) = identifier(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag("=")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, expression) = expression(input)?;
-  Ok((input, Node::VariableDefine{ children: vec![variable, expression]}))   
+
+pub fn expression(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (result, syntax)) = alt((if_stmt, math_expression))(input)?;
+  Ok((input, (Node::Expression{ children: vec![result]}, syntax.node(SyntaxKind::Expression))))
 }
-pub fn arguments(input: &str) -> IResult<&str, Node> {
-  let (input, arg) = expression(input)?;
-  let (input, mut others) = many0(other_arg)(input)?;
+
+pub fn statement(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (result, syntax)) = alt((variable_define, function_return, expression_statement))(input)?;
+  let (input, semicolon) = punctuation(";")(input)?;
+  Ok((input, (Node::Statement{ children: vec![result]}, syntax.then(semicolon).node(SyntaxKind::Statement))))
+}
+// An expression run for its effect, such as `println(x);`. An if is already a statement, so
+// only math expressions are tried here.
+pub fn expression_statement(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (result, syntax)) = math_expression(input)?;
+  Ok((input, (Node::Expression{ children: vec![result]}, syntax.node(SyntaxKind::Expression))))
+}
+// A bare `return;` has no value
+pub fn function_return(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, return_keyword) = keyword("return")(input)?;
+  let (input, return_value) = opt(expression)(input)?;
+  let (children, syntax): (Vec<Node>, Syntax) = return_value.into_iter().unzip();
+  Ok((input, (Node::FunctionReturn{ children }, Syntax::from(return_keyword).then(syntax).node(SyntaxKind::FunctionReturn))))
+}
+// An annotated variable keeps its TypeAnnotation after the expression, so children[1] is always the value
+pub fn variable_define(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, let_keyword) = keyword("let")(input)?;
+  let (input, (variable, name)) = new_name(input)?;
+  let (input, annotation) = opt(type_annotation)(input)?;
+  let (input, equals) = operator("=")(input)?;
+  let (input, (expression, value)) = expression(input)?;
+  let (annotation, annotated): (Vec<Node>, Syntax) = annotation.into_iter().unzip();
+  let mut children = vec![variable, expression];
+  children.extend(annotation);
+  let syntax = Syntax::from(let_keyword).then(name).then(annotated).then(equals).then(value);
+  Ok((input, (Node::VariableDefine{ children }, syntax.node(SyntaxKind::VariableDefine))))
+}
+// Type names are ordinary names everywhere but here, except nil, which is a keyword. The
+// syntax has the name as a keyword without the node around it, which also takes the
+// colon or arrow before it.
+pub fn type_name(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = alt((any(TokenKind::Identifier), keyword("nil")))(input)?;
+  if !TYPE_NAMES.contains(&result.text.as_str()) {
+    return Err(nom::Err::Error((input, ErrorKind::Tag)));
+  }
+  Ok((input, (Node::TypeAnnotation{ value: result.text.clone()}, Syntax::token(TokenKind::Keyword, result))))
+}
+pub fn type_annotation(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, colon) = punctuation(":")(input)?;
+  let (input, (annotation, syntax)) = type_name(input)?;
+  Ok((input, (annotation, Syntax::from(colon).then(syntax).node(SyntaxKind::TypeAnnotation))))
+}
+pub fn return_type(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, arrow) = punctuation("->")(input)?;
+  let (input, (annotation, syntax)) = type_name(input)?;
+  Ok((input, (annotation, Syntax::from(arrow).then(syntax).node(SyntaxKind::TypeAnnotation))))
+}
+// Parameters have the same shape as call arguments, with an optional TypeAnnotation after the name
+pub fn parameter(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (name, syntax)) = new_name(input)?;
+  let (input, annotation) = opt(type_annotation)(input)?;
+  let (annotation, annotated): (Vec<Node>, Syntax) = annotation.into_iter().unzip();
+  let mut children = vec![name];
+  children.extend(annotation);
+  Ok((input, (Node::Expression{ children }, syntax.then(annotated).node(SyntaxKind::Expression))))
+}
+pub fn parameters(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (param, syntax)) = parameter(input)?;
+  let (input, others) = many0(other_parameter)(input)?;
+  let (mut others, rest): (Vec<Node>, Syntax) = others.into_iter().unzip();
+  let mut params = vec![param];
+  params.append(&mut others);
+  Ok((input, (Node::FunctionArguments{children: params}, syntax.then(rest).node(SyntaxKind::FunctionArguments))))
+}
+pub fn other_parameter(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, comma) = punctuation(",")(input)?;
+  let (input, (param, syntax)) = parameter(input)?;
+  Ok((input, (param, Syntax::from(comma).then(syntax))))
+}
+pub fn arguments(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, (arg, syntax)) = expression(input)?;
+  let (input, others) = many0(other_arg)(input)?;
+  let (mut others, rest): (Vec<Node>, Syntax) = others.into_iter().unzip();
   let mut args = vec![arg];
   args.append(&mut others);
-  Ok((input, Node::FunctionArguments{children: args}))
-}
-pub fn other_arg(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag(",")(input)?;
-  expression(input)
-}
-pub fn function_definition(input: &str) -> IResult<&str, Node> {
-  let (input, _) = tag("fn ")(input)?;
-  let (input, function_name) = identifier(input)?;
-  let (input, _) = tag("(")(input)?;
-  let (input, mut args) = many0(arguments)(input)?;
-  let (input, _) = tag(")")(input)?;
-  let (input, _) = many0(tag(" "))(input)?;
-  let (input, _) = tag("{")(input)?;
-  let (input, _) = many0(tag("\n"))(input)?;
-  let (input, mut statements) = many1(alt((statement, if_stmt)))(input)?;
-  let (input, _) = tag("}")(input)?;
-  let (input, _) = many0(alt((tag("\n"),tag(" "))))(input)?;
+  Ok((input, (Node::FunctionArguments{children: args}, syntax.then(rest).node(SyntaxKind::FunctionArguments))))
+}
+pub fn other_arg(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, comma) = punctuation(",")(input)?;
+  let (input, (arg, syntax)) = expression(input)?;
+  Ok((input, (arg, Syntax::from(comma).then(syntax))))
+}
+pub fn function_definition(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, fn_keyword) = keyword("fn")(input)?;
+  let (input, (function_name, name)) = new_name(input)?;
+  let (input, open) = punctuation("(")(input)?;
+  let (input, args) = opt(parameters)(input)?;
+  let (input, close) = punctuation(")")(input)?;
+  let (input, returns) = opt(return_type)(input)?;
+  let (input, (mut statements, body)) = body(input)?;
+  let (args, params): (Vec<Node>, Syntax) = args.into_iter().unzip();
+  let (returns, annotated): (Vec<Node>, Syntax) = returns.into_iter().unzip();
   let mut children = vec![function_name];
-  children.append(&mut args);
+  children.extend(args);
+  children.extend(returns);
   children.append(&mut statements);
-  Ok((input, Node::FunctionDefine{ children: children }))   
+  let syntax = Syntax::from(fn_keyword).then(name).then(open).then(params).then(close).then(annotated).then(body);
+  Ok((input, (Node::FunctionDefine{ children }, syntax.node(SyntaxKind::FunctionDefine))))
+}
+// A program is any number of functions, statements and expressions, one after another. Its
+// syntax is left open, as the tree's root is made around it along with the trivia.
+pub fn items(input: Tokens) -> IResult<Tokens, Parsed> {
+  let (input, result) = many1(alt((function_definition, statement, expression)))(input)?;
+  let (children, syntax): (Vec<Node>, Syntax) = result.into_iter().unzip();
+  Ok((input, (Node::Program{ children }, syntax)))
 }
-// Define a program. You will change this, this is just here for example.
-// You'll probably want to modify this by changing it to be that a program
-// is defined as at least one function definition, but maybe more. Start
-// by looking up the many1() combinator and that should get you started.
+
+// Lexes the source and parses its tokens. What is left over, or where parsing failed, is
+// given back as the rest of the source from the first token that wasn't taken.
+fn parse_items(input: &str) -> IResult<&str, Parsed> {
+  let tokens: Vec<Token> = tokenize(input).into_iter().filter(|t| !t.kind.is_trivia()).collect();
+  let rest = |tokens: Tokens| tokens.first().map_or(&input[input.len()..], |t| &input[t.offset..]);
+  match items(&tokens) {
+    Ok((remaining, parsed)) => Ok((rest(remaining), parsed)),
+    Err(nom::Err::Error((remaining, kind))) => Err(nom::Err::Error((rest(remaining), kind))),
+    Err(nom::Err::Failure((remaining, kind))) => Err(nom::Err::Failure((rest(remaining), kind))),
+    Err(nom::Err::Incomplete(needed)) => Err(nom::Err::Incomplete(needed)),
+  }
+}
+
 pub fn program(input: &str) -> IResult<&str, Node> {
-  let (input, result) = many1(alt((function_definition, statement, expression)))(input)?;  // Now that we've defined a number and an identifier, we can compose them using more combinators. Here we use the "alt" combinator to propose a choice.
-  Ok((input, Node::Program{ children: result}))       // Whether the result is an identifier or a number, we attach that to the program
+  let (rest, (ast, _)) = parse_items(input)?;
+  Ok((rest, ast))
+}
+
+// Parses a program into its tree along with the lossless syntax tree of the source it
+// took, which keeps the comments, whitespace and parentheses the tree leaves out
+pub fn program_with_syntax(input: &str) -> IResult<&str, (Node, SyntaxNode)> {
+  let (rest, (ast, syntax)) = parse_items(input)?;
+  Ok((rest, (ast, syntax.tree(&input[..input.len() - rest.len()]))))
+}
+
+// A syntax error: where in the source, and what is wrong there
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+  pub span: Range<usize>,
+  pub message: String,
+}
+
+// What went wrong with a parse of source, or None when it took the whole source. Parsing
+// stops at the first thing that isn't a function, statement or expression, and the error
+// is about the line from there, unless a keyword was used as a name.
+pub fn parse_error<T>(source: &str, result: &IResult<&str, T>) -> Option<ParseError> {
+  let (rest, reserved) = match result {
+    Ok((rest, _)) if rest.trim().is_empty() => return None,
+    Ok((rest, _)) | Err(nom::Err::Error((rest, _))) => (rest.trim_start(), false),
+    Err(nom::Err::Failure((rest, _))) => (rest.trim_start(), true),
+    Err(nom::Err::Incomplete(_)) => ("", false),
+  };
+  Some(error_at(source, source.len() - rest.len(), source.len(), reserved))
+}
+
+// The error for parsing that stopped at start: the keyword there when one was used as a
+// name, or else what follows on the line, up to end
+fn error_at(source: &str, start: usize, end: usize, reserved: bool) -> ParseError {
+  let rest = &source[start..];
+  if reserved {
+    let word = &rest[..rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len())];
+    let message = format!("`{}` is a keyword and can't be used as a name", word);
+    return ParseError{span: start..start + word.len(), message};
+  }
+  match source[start..end.max(start)].lines().next() {
+    Some(found) => ParseError{span: start..start + found.len(), message: format!("unexpected `{}`", found)},
+    None => ParseError{span: start..start, message: "unexpected end of file".to_string()},
+  }
+}
+
+// Skips the tokens of an item that didn't parse: up to and including the next `;` or `}`
+// outside any braces, or up to the next function definition, whichever comes first
+fn synchronize(input: Tokens) -> Tokens {
+  let mut depth = 0;
+  for (ix, token) in input.iter().enumerate() {
+    let next = input.get(ix + 1).map(|t| t.kind);
+    match (token.kind, token.text.as_str()) {
+      (TokenKind::Keyword, "fn") if ix > 0 && next == Some(TokenKind::Identifier) => return &input[ix..],
+      (TokenKind::Punctuation, "{") => depth += 1,
+      (TokenKind::Punctuation, "}") if depth <= 1 => return &input[ix + 1..],
+      (TokenKind::Punctuation, "}") => depth -= 1,
+      (TokenKind::Punctuation, ";") if depth == 0 => return &input[ix + 1..],
+      _ => (),
+    }
+  }
+  &input[input.len()..]
+}
+
+// Parses every item it can, along with the syntax of them all. After an item that doesn't
+// parse, the error is noted and parsing starts again past it, so one mistake doesn't hide
+// the ones after it.
+fn recover(source: &str) -> (Parsed, Vec<ParseError>) {
+  let tokens: Vec<Token> = tokenize(source).into_iter().filter(|t| !t.kind.is_trivia()).collect();
+  let offset = |input: Tokens| input.first().map_or(source.len(), |t| t.offset);
+  let mut input: Tokens = &tokens;
+  let mut items = vec![];
+  let mut syntax = Syntax::default();
+  let mut errors = vec![];
+  if input.is_empty() {
+    errors.push(error_at(source, source.len(), source.len(), false));
+  }
+  while let Some(first) = input.first() {
+    let taken = |remaining: Tokens| {
+      let last = &input[input.len() - remaining.len() - 1];
+      first.offset..last.offset + last.text.len()
+    };
+    match alt((function_definition, statement, expression))(input) {
+      Ok((remaining, (node, item))) => {
+        items.push(node);
+        syntax = syntax.then(item);
+        input = remaining;
+      },
+      Err(e) => {
+        let (at, reserved) = match e {
+          nom::Err::Error((at, _)) => (at, false),
+          nom::Err::Failure((at, _)) => (at, true),
+          nom::Err::Incomplete(_) => (input, false),
+        };
+        let remaining = synchronize(input);
+        errors.push(error_at(source, offset(at), taken(remaining).end, reserved));
+        input = remaining;
+      },
+    }
+  }
+  ((Node::Program{children: items}, syntax), errors)
+}
+
+// Parses the whole source, going on past syntax errors. The program holds every function,
+// statement and expression that parsed, and the errors are in source order.
+pub fn parse(source: &str) -> (Node, Vec<ParseError>) {
+  let ((ast, _), errors) = recover(source);
+  (ast, errors)
+}
+
+// The same, along with the syntax tree. The stretches that didn't parse are in it as plain
+// tokens, so it still gives back the whole source.
+pub fn parse_with_syntax(source: &str) -> (Node, SyntaxNode, Vec<ParseError>) {
+  let ((ast, syntax), errors) = recover(source);
+  (ast, syntax.tree(source), errors)
 }
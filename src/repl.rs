@@ -0,0 +1,116 @@
+use crate::parse;
+use cse262_project::{outline, Interpreter, Node, Value};
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = ":ast code   show how code parses, without running it
+:load file  run a file in this session
+:reset      forget every function and variable
+:help       show this list
+:quit       leave, as does the end of input";
+
+// How many more `{` than `}` the source has. While any are left open the entry continues
+// on the next line. Strings can't hold braces, so every one outside a comment counts.
+fn open_braces(source: &str) -> i32 {
+  source.lines().flat_map(|line| line.split("//").next()).flat_map(str::chars).map(|c| match c {
+    '{' => 1,
+    '}' => -1,
+    _ => 0,
+  }).sum()
+}
+
+// True when the program does more than define functions, so there is something to run
+fn has_statements(ast: &Node) -> bool {
+  match ast {
+    Node::Program{children} => children.iter().any(|n| !matches!(n, Node::FunctionDefine{..})),
+    _ => false,
+  }
+}
+
+// Runs an entry against the session. Definitions and globals stay for later entries.
+fn evaluate(interpreter: &mut Interpreter, source: &str) {
+  let ast = match parse(source) {
+    Ok(ast) => ast,
+    Err(e) => return eprintln!("{}", e),
+  };
+  let result = if has_statements(&ast) {
+    interpreter.run(&ast)
+  } else {
+    interpreter.load(&ast).map(|_| Value::Nil)
+  };
+  match result {
+    Ok(Value::Nil) => (),
+    Ok(value) => println!("{}", value),
+    Err(e) => eprintln!("runtime error: {}", e),
+  }
+}
+
+// Returns false when the session should end
+fn command(interpreter: &mut Interpreter, line: &str) -> bool {
+  let (name, argument) = match line.split_once(char::is_whitespace) {
+    Some((name, argument)) => (name, argument.trim()),
+    None => (line, ""),
+  };
+  match name {
+    ":ast" => match parse(argument) {
+      Ok(ast) => print!("{}", outline(&ast)),
+      Err(e) => eprintln!("{}", e),
+    },
+    ":load" => match fs::read_to_string(argument) {
+      Ok(source) => evaluate(interpreter, &source),
+      Err(e) => eprintln!("cannot read {}: {}", argument, e),
+    },
+    ":reset" => *interpreter = Interpreter::new(),
+    ":help" => println!("{}", HELP),
+    ":quit" => return false,
+    _ => eprintln!("unknown command {}, try :help", name),
+  }
+  true
+}
+
+// The toolchain CI builds with can't ask std whether stdin is a terminal, so the C
+// library is asked instead. Elsewhere a terminal is assumed.
+#[cfg(unix)]
+fn stdin_is_terminal() -> bool {
+  extern "C" {
+    fn isatty(fd: i32) -> i32;
+  }
+  unsafe { isatty(0) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdin_is_terminal() -> bool {
+  true
+}
+
+// Reads entries from stdin until it ends. Prompts are only shown to a terminal, so
+// piped input gives just the output.
+pub fn repl() {
+  let stdin = io::stdin();
+  let interactive = stdin_is_terminal();
+  let mut interpreter = Interpreter::new();
+  let mut entry = String::new();
+  loop {
+    if interactive {
+      print!("{}", if entry.is_empty() { "> " } else { "... " });
+      let _ = io::stdout().flush();
+    }
+    let mut line = String::new();
+    match stdin.lock().read_line(&mut line) {
+      Ok(0) | Err(_) => break,
+      Ok(_) => entry.push_str(&line),
+    }
+    if open_braces(&entry) > 0 {
+      continue;
+    }
+    let source = std::mem::take(&mut entry);
+    let source = source.trim();
+    if source.starts_with(':') {
+      if !command(&mut interpreter, source) {
+        break;
+      }
+    } else if !source.is_empty() {
+      evaluate(&mut interpreter, source);
+    }
+  }
+}
@@ -0,0 +1,343 @@
+use crate::builtins::builtin;
+use crate::parser::Node;
+use crate::syntax::{in_source_order, Spans};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolveError {
+  pub function: String,
+  pub message: String,
+}
+
+impl fmt::Display for ResolveError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "in function `{}`: {}", self.function, self.message)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NameKind {
+  Function,
+  // A builtin or host function, which has no definition in the source
+  Builtin,
+  Variable,
+}
+
+// A name in the source and the definition it refers to, which is None when there isn't
+// one. Variables carry the function whose variable they are, `main` for globals.
+pub(crate) struct Reference {
+  pub(crate) span: Range<usize>,
+  pub(crate) name: String,
+  pub(crate) kind: NameKind,
+  pub(crate) definition: Option<Range<usize>>,
+  pub(crate) function: String,
+}
+
+// A function's variables in slot order, each with where it is first defined
+pub(crate) struct FunctionScope {
+  pub(crate) function: String,
+  pub(crate) span: Range<usize>,
+  pub(crate) variables: Vec<(String, Range<usize>)>,
+}
+
+// Where every name in a program is and what it refers to, for editors
+pub(crate) struct Names {
+  pub(crate) errors: Vec<(Range<usize>, ResolveError)>,
+  pub(crate) references: Vec<Reference>,
+  pub(crate) scopes: Vec<FunctionScope>,
+  // The variables of the top level statements, which functions can also read
+  pub(crate) globals: Vec<(String, Range<usize>)>,
+}
+
+// Variables are function scoped, so every name in a function gets one slot, parameters
+// first. Each slot keeps where its name is first defined.
+#[derive(Default)]
+struct Scope {
+  slots: HashMap<String, usize>,
+  variables: Vec<(String, Range<usize>)>,
+}
+
+impl Scope {
+  fn define(&mut self, name: &str, span: Range<usize>) -> usize {
+    if let Some(slot) = self.slots.get(name) {
+      return *slot;
+    }
+    self.slots.insert(name.to_string(), self.variables.len());
+    self.variables.push((name.to_string(), span));
+    self.variables.len() - 1
+  }
+
+  fn definition(&self, slot: usize) -> Range<usize> {
+    self.variables[slot].1.clone()
+  }
+}
+
+struct Resolver<'a> {
+  // Where each node is, when the resolver is given the syntax tree's spans
+  spans: Option<&'a Spans<'a>>,
+  // Each function with where its name is first defined
+  functions: HashMap<String, Range<usize>>,
+  // Functions the host registered, which a script function of the same name replaces
+  hosts: HashSet<String>,
+  // Variables bound by top level lets, which functions can read. The top level itself
+  // binds them in order like any other scope.
+  globals: HashMap<String, Range<usize>>,
+  top_level: bool,
+  function: String,
+  errors: Vec<(Range<usize>, ResolveError)>,
+  references: Vec<Reference>,
+  scopes: Vec<FunctionScope>,
+  main: Vec<(String, Range<usize>)>,
+}
+
+impl<'a> Resolver<'a> {
+
+  fn new(spans: Option<&'a Spans<'a>>, hosts: &[&str]) -> Resolver<'a> {
+    Resolver{
+      spans,
+      functions: HashMap::new(),
+      hosts: hosts.iter().map(|name| name.to_string()).collect(),
+      globals: HashMap::new(),
+      top_level: false,
+      function: String::new(),
+      errors: vec![],
+      references: vec![],
+      scopes: vec![],
+      main: vec![],
+    }
+  }
+
+  fn span(&self, node: &Node) -> Range<usize> {
+    self.spans.map_or(0..0, |spans| spans.get(node))
+  }
+
+  fn error(&mut self, span: Range<usize>, message: String) {
+    self.errors.push((span, ResolveError{function: self.function.clone(), message}));
+  }
+
+  fn reference(&mut self, span: Range<usize>, name: &str, kind: NameKind, definition: Option<Range<usize>>, function: &str) {
+    let (name, function) = (name.to_string(), function.to_string());
+    self.references.push(Reference{span, name, kind, definition, function});
+  }
+
+  fn resolve_root(&mut self, node: &Node) -> Node {
+    match node {
+      Node::Program{children} => self.resolve_program(children),
+      _ => self.resolve(node, &mut Scope::default()),
+    }
+  }
+
+  fn resolve_program(&mut self, children: &[Node]) -> Node {
+    for n in children {
+      match n {
+        Node::FunctionDefine{children} => {
+          if let Some(Node::Identifier{value}) = children.first() {
+            let span = self.span(&children[0]);
+            if self.functions.contains_key(value) {
+              self.function = value.clone();
+              self.error(span, format!("function `{}` is defined more than once", value));
+            } else {
+              self.functions.insert(value.clone(), span);
+            }
+          }
+        },
+        _ => self.find_globals(n),
+      }
+    }
+    // Top level statements run as the body of main, in order
+    let mut main = Scope::default();
+    let mut resolved = vec![];
+    for n in children {
+      match n {
+        Node::FunctionDefine{children} => resolved.push(self.resolve_function(n, children)),
+        _ => {
+          self.function = "main".to_string();
+          self.top_level = true;
+          resolved.push(self.resolve(n, &mut main));
+          self.top_level = false;
+        },
+      }
+    }
+    self.main = main.variables;
+    Node::Program{children: resolved}
+  }
+
+  // Every let the top level runs binds a global, including those in its ifs and whiles
+  fn find_globals(&mut self, node: &Node) {
+    if let Node::VariableDefine{children} = node {
+      if let Some(Node::Identifier{value}) = children.first() {
+        let span = self.span(&children[0]);
+        self.globals.entry(value.clone()).or_insert(span);
+      }
+    }
+    for child in in_source_order(node) {
+      self.find_globals(child);
+    }
+  }
+
+  fn resolve_function(&mut self, node: &Node, children: &[Node]) -> Node {
+    if let Some(Node::Identifier{value}) = children.first() {
+      self.function = value.clone();
+      let definition = self.functions.get(value).cloned();
+      self.reference(self.span(&children[0]), value, NameKind::Function, definition, "");
+    }
+    let mut scope = Scope::default();
+    let mut resolved = vec![];
+    for n in children {
+      match n {
+        Node::FunctionArguments{children} => {
+          let mut params = vec![];
+          for param in children {
+            match param {
+              Node::Expression{children} => {
+                let mut resolved = children.clone();
+                if let Some(Node::Identifier{value}) = children.first() {
+                  let span = self.span(&children[0]);
+                  if scope.slots.contains_key(value) {
+                    self.error(span.clone(), format!("parameter `{}` is declared more than once", value));
+                  }
+                  let slot = scope.define(value, span.clone());
+                  let function = self.function.clone();
+                  self.reference(span, value, NameKind::Variable, Some(scope.definition(slot)), &function);
+                  resolved[0] = Node::Local{value: value.clone(), slot};
+                }
+                params.push(Node::Expression{children: resolved});
+              },
+              _ => params.push(param.clone()),
+            }
+          }
+          resolved.push(Node::FunctionArguments{children: params});
+        },
+        // The function name is bound by the program, not the function's own scope
+        Node::Identifier{..} if resolved.is_empty() => resolved.push(n.clone()),
+        _ => resolved.push(self.resolve(n, &mut scope)),
+      }
+    }
+    let (function, span) = (self.function.clone(), self.span(node));
+    self.scopes.push(FunctionScope{function, span, variables: scope.variables});
+    Node::FunctionDefine{children: resolved}
+  }
+
+  fn resolve_all(&mut self, nodes: &[Node], scope: &mut Scope) -> Vec<Node> {
+    nodes.iter().map(|n| self.resolve(n, scope)).collect()
+  }
+
+  fn resolve(&mut self, node: &Node, scope: &mut Scope) -> Node {
+    match node {
+      Node::Identifier{value} => {
+        let (span, function) = (self.span(node), self.function.clone());
+        match scope.slots.get(value) {
+          Some(&slot) => {
+            self.reference(span, value, NameKind::Variable, Some(scope.definition(slot)), &function);
+            Node::Local{value: value.clone(), slot}
+          },
+          // Left unresolved, so it is looked up among the globals when it runs
+          None => match self.globals.get(value).cloned() {
+            Some(definition) if !self.top_level => {
+              self.reference(span, value, NameKind::Variable, Some(definition), "main");
+              node.clone()
+            },
+            _ => {
+              self.error(span.clone(), format!("undefined variable `{}`", value));
+              self.reference(span, value, NameKind::Variable, None, &function);
+              node.clone()
+            },
+          },
+        }
+      },
+      Node::FunctionCall{name, children} => {
+        // The name is the first thing in the call
+        let call = self.span(node);
+        let span = call.start..call.end.min(call.start + name.len());
+        match self.functions.get(name).cloned() {
+          Some(definition) => self.reference(span, name, NameKind::Function, Some(definition), ""),
+          None if self.hosts.contains(name) || builtin(name).is_some() => self.reference(span, name, NameKind::Builtin, None, ""),
+          None => {
+            self.error(span.clone(), format!("undefined function `{}`", name));
+            self.reference(span, name, NameKind::Function, None, "");
+          },
+        }
+        Node::FunctionCall{name: name.clone(), children: self.resolve_all(children, scope)}
+      },
+      Node::VariableDefine{children} => {
+        // The value is resolved first, so `let x = x + 1;` refers to the previous x
+        let value = self.resolve(&children[1], scope);
+        let mut resolved = children.clone();
+        if let Node::Identifier{value: name} = &children[0] {
+          let span = self.span(&children[0]);
+          let slot = scope.define(name, span.clone());
+          let function = self.function.clone();
+          self.reference(span, name, NameKind::Variable, Some(scope.definition(slot)), &function);
+          resolved[0] = Node::Local{value: name.clone(), slot};
+        }
+        resolved[1] = value;
+        Node::VariableDefine{children: resolved}
+      },
+      Node::Program{children} => Node::Program{children: self.resolve_all(children, scope)},
+      Node::Statement{children} => Node::Statement{children: self.resolve_all(children, scope)},
+      Node::FunctionReturn{children} => Node::FunctionReturn{children: self.resolve_all(children, scope)},
+      Node::FunctionDefine{children} => Node::FunctionDefine{children: self.resolve_all(children, scope)},
+      Node::FunctionArguments{children} => Node::FunctionArguments{children: self.resolve_all(children, scope)},
+      Node::FunctionStatements{children} => Node::FunctionStatements{children: self.resolve_all(children, scope)},
+      Node::Expression{children} => Node::Expression{children: self.resolve_all(children, scope)},
+      Node::TestEquality{children} => Node::TestEquality{children: self.resolve_all(children, scope)},
+      Node::Condition{conditions} => Node::Condition{conditions: self.resolve_all(conditions, scope)},
+      Node::MathExpression{name, children} => {
+        Node::MathExpression{name: name.clone(), children: self.resolve_all(children, scope)}
+      },
+      Node::ConditionExpression{name, children} => {
+        Node::ConditionExpression{name: name.clone(), children: self.resolve_all(children, scope)}
+      },
+      // In the order they are written, so a branch sees the variables the ones before it define
+      Node::If{condition, children} => {
+        let (mut conditions, mut bodies) = (vec![], vec![]);
+        for (ix, body) in children.iter().enumerate() {
+          if let Some(c) = condition.get(ix) {
+            conditions.push(self.resolve(c, scope));
+          }
+          bodies.push(self.resolve(body, scope));
+        }
+        Node::If{condition: conditions, children: bodies}
+      },
+      Node::While{condition, children} => {
+        let condition = self.resolve_all(condition, scope);
+        Node::While{condition, children: self.resolve_all(children, scope)}
+      },
+      Node::Number{..} |
+      Node::Bool{..} |
+      Node::String{..} |
+      Node::Nil |
+      Node::TypeAnnotation{..} |
+      Node::Local{..} => node.clone(),
+    }
+  }
+
+}
+
+// Binds every variable and function call to its definition. On success the returned tree
+// has each variable replaced by a Local carrying its slot in the function's frame.
+// Otherwise every unresolved name and duplicate definition is reported together.
+pub fn resolve(node: &Node) -> Result<Node, Vec<ResolveError>> {
+  resolve_with_hosts(node, &[])
+}
+
+// The same, for a program that can also call the host functions named
+pub fn resolve_with_hosts(node: &Node, hosts: &[&str]) -> Result<Node, Vec<ResolveError>> {
+  let mut resolver = Resolver::new(None, hosts);
+  let resolved = resolver.resolve_root(node);
+  if resolver.errors.is_empty() {
+    Ok(resolved)
+  } else {
+    Err(resolver.errors.into_iter().map(|(_, e)| e).collect())
+  }
+}
+
+// Resolves a program the same way, keeping where each name is in the source the spans
+// were made from
+pub(crate) fn resolve_names(node: &Node, spans: &Spans) -> Names {
+  let mut resolver = Resolver::new(Some(spans), &[]);
+  resolver.resolve_root(node);
+  Names{errors: resolver.errors, references: resolver.references, scopes: resolver.scopes, globals: resolver.main}
+}
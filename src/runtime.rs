@@ -1,161 +1,255 @@
+use crate::builtins::builtin;
+use crate::interpreter::Interpreter;
+use crate::output::Output;
 use crate::parser::Node;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// Errors are static messages, so they can be compared in tests and returned by host functions
+pub type RuntimeError = &'static str;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
   String(String),
   Number(i32),
   Bool(bool),
-  Ignore()
+  Nil,
+  List(Vec<Value>),
+  Map(HashMap<String, Value>),
+}
+
+// How print writes a value. Strings are written without quotes, and map keys are sorted
+// so the output doesn't depend on hashing.
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Value::String(value) => write!(f, "{}", value),
+      Value::Number(value) => write!(f, "{}", value),
+      Value::Bool(value) => write!(f, "{}", value),
+      Value::Nil => write!(f, "nil"),
+      Value::List(list) => {
+        write!(f, "[")?;
+        for (ix, value) in list.iter().enumerate() {
+          if ix > 0 {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", value)?;
+        }
+        write!(f, "]")
+      },
+      Value::Map(map) => {
+        let mut keys: Vec<&String> = map.keys().collect();
+        keys.sort();
+        write!(f, "{{")?;
+        for (ix, key) in keys.into_iter().enumerate() {
+          if ix > 0 {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}: {}", key, map[key])?;
+        }
+        write!(f, "}}")
+      },
+    }
+  }
+}
+
+// How deeply calls may nest before running stops with "Stack overflow". Tail calls replace
+// the caller's frame, so they don't count. The tree walker nests on the caller's stack, up
+// to about 16 KiB a call in a debug build, so the default fits the 2 MiB stack of a
+// spawned thread. A host running scripts on a bigger stack can raise it to match.
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+// Limits for running untrusted scripts. Each one stops the script with its own error:
+// "Step limit exceeded", "Time limit exceeded", "Stack overflow" and "Size limit exceeded".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunLimits {
+  // Nodes evaluated by the tree walker, or instructions executed by the virtual machine
+  pub max_steps: Option<u64>,
+  pub timeout: Option<Duration>,
+  pub max_depth: usize,
+  // Longest string, in bytes, or most elements in a list or map a script may create
+  pub max_size: Option<usize>,
+}
+
+impl Default for RunLimits {
+  fn default() -> RunLimits {
+    RunLimits {
+      max_steps: None,
+      timeout: None,
+      max_depth: DEFAULT_MAX_DEPTH,
+      max_size: None,
+    }
+  }
+}
+
+// Reading the clock costs more than a step, so it is only checked every this many steps
+const CLOCK_INTERVAL: u64 = 1024;
+
+// Tracks a run against its limits, shared by the tree walker and the virtual machine
+pub(crate) struct Meter {
+  pub(crate) limits: RunLimits,
+  steps: u64,
+  deadline: Option<Instant>,
+}
+
+impl Meter {
+
+  pub(crate) fn new(limits: &RunLimits) -> Meter {
+    Meter {
+      limits: *limits,
+      steps: 0,
+      deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+    }
+  }
+
+  pub(crate) fn step(&mut self) -> Result<(), &'static str> {
+    self.steps += 1;
+    if self.limits.max_steps.map_or(false, |max| self.steps > max) {
+      return Err("Step limit exceeded");
+    }
+    if self.steps % CLOCK_INTERVAL == 0 && self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+      return Err("Time limit exceeded");
+    }
+    Ok(())
+  }
+
+  pub(crate) fn check_size(&self, value: &Value) -> Result<(), &'static str> {
+    let size = match value {
+      Value::String(s) => s.len(),
+      Value::List(list) => list.len(),
+      Value::Map(map) => map.len(),
+      _ => 0,
+    };
+    match self.limits.max_size {
+      Some(max) if size > max => Err("Size limit exceeded"),
+      _ => Ok(()),
+    }
+  }
+
+}
+
+// A call frame. Variables bound by the resolver live in slots, the rest are looked up by name.
+#[derive(Default)]
+struct Frame {
+  variables: HashMap<String, Value>,
+  slots: Vec<Option<Value>>,
+  // The program's top level statements bind globals instead of locals
+  global: bool,
+}
+
+impl Frame {
+  fn bind(&mut self, target: &Node, value: Value) {
+    match target {
+      Node::Identifier{value: name} => {
+        self.variables.insert(name.clone(), value);
+      },
+      Node::Local{slot, ..} => {
+        if self.slots.len() <= *slot {
+          self.slots.resize(*slot + 1, None);
+        }
+        self.slots[*slot] = Some(value);
+      },
+      _ => (),
+    }
+  }
+
+  // Binds an argument to its parameter, checking it against the parameter's annotation
+  fn bind_param(&mut self, param: &Node, value: Value) -> Result<(), RuntimeError> {
+    if let Node::Expression{children} = param {
+      if let Some(Node::TypeAnnotation{value: annotation}) = children.get(1) {
+        if !has_type(&value, annotation) {
+          return Err("Argument does not match its type");
+        }
+      }
+      self.bind(&children[0], value);
+    }
+    Ok(())
+  }
+}
+
+// A function provided by the program embedding the interpreter
+pub type HostFunction = Rc<dyn Fn(&[Value]) -> Result<Value, RuntimeError>>;
+
+// A host function, called with exactly arity arguments
+#[derive(Clone)]
+pub(crate) struct Host {
+  pub(crate) arity: usize,
+  pub(crate) function: HostFunction,
 }
 
+// How a block of statements finished. A tail call carries the function to run in place
+// of the current one, with its arguments already bound.
+enum Flow {
+  Return(Value),
+  TailCall(Rc<[Node]>, Frame),
+}
 
-struct Runtime {
-  functions: HashMap<String, Vec<Node>>,
-  stack: Vec<HashMap<String, Value>>,
+pub(crate) struct Runtime {
+  functions: HashMap<String, Rc<[Node]>>,
+  pub(crate) host: HashMap<String, Host>,
+  pub(crate) globals: HashMap<String, Value>,
+  // The body of main made from the top level statements, if that is what main is
+  top_level: Option<Rc<[Node]>>,
+  stack: Vec<Frame>,
+  pub(crate) meter: Meter,
+  pub(crate) output: Output,
 }
 
 impl Runtime {
 
-  pub fn new() -> Runtime {
+  pub fn new(limits: &RunLimits) -> Runtime {
     Runtime {
       functions: HashMap::new(),
+      host: HashMap::new(),
+      globals: HashMap::new(),
+      top_level: None,
       stack: Vec::new(),
+      meter: Meter::new(limits),
+      output: Output::stdout(),
     }
   }
 
   pub fn run(&mut self, node: &Node) -> Result<Value, &'static str> {
+    self.meter.step()?;
     match node {
-      Node::Program{children} => {
-        for n in children {
-          match n {
-            Node::FunctionDefine{..} => {
-              self.run(n);
-            },
-            Node::Expression{..} => {
-              self.functions.insert("main".to_string(), vec![Node::FunctionReturn{children: vec![n.clone()]}]);
-            },
-            Node::Statement{..} => {
-              self.functions.insert("main".to_string(), vec![n.clone()]);
-            }
-            _ => (),
-          }
-        }
-        Ok(Value::Bool(true))
-      },
-      Node::MathExpression{name, children} => {
-        match (self.run(&children[0]), self.run(&children[1])) {
-          (Ok(Value::Number(lhs)), Ok(Value::Number(rhs))) => {
-            match name.as_ref() {
-              "+" => Ok(Value::Number(lhs + rhs)),
-              "-" => Ok(Value::Number(lhs - rhs)),
-              "*" => Ok(Value::Number(lhs * rhs)),
-              "/" => Ok(Value::Number(lhs / rhs)),
-              "^" => {
-                let mut result = 1;
-                for i in 0..rhs {
-                  result = result * lhs;
-                }
-                Ok(Value::Number(result))
-              },
-              _ => Err("Undefined operator"),
-            }
-          }
-          _ => Err("Cannot do math on String or Bool"),
-        }
-      },
+      Node::Program{children} => self.define(children),
+      Node::MathExpression{name, children} => self.binary(name, children, math),
       Node::FunctionCall{name, children} => {
-        let in_args = if children.len() > 0 {
-          match &children[0] {
-            Node::FunctionArguments{children} => {
-              children
-            },
-            _ => children,
-          }
-        } else {
-          children
-        };
-        let mut new_frame = HashMap::new();
-        let mut result: Result<Value, &'static str> = Err("Undefined function");
-        let rt = self as *mut Runtime;
-        match self.functions.get(name) {
-          Some(statements) => {
-            {
-              match statements[0].clone() { 
-                Node::FunctionArguments{children} => {
-                  
-                  for (ix, arg) in children.iter().enumerate() {
-                    unsafe {
-                      let result = (*rt).run(&in_args[ix])?;
-                      match arg {
-                        Node::Expression{children} => {
-                          match &children[0] {
-                            Node::Identifier{value} => {
-                              new_frame.insert(value.clone(),result);
-                            },
-                            _ => (),
-                          }
-                        }
-                        _ => (),
-                      }
-                    }
-                  }
-                }
-                _ => (),
-              }     
-            }  
-            self.stack.push(new_frame);
-            for n in statements.clone() {
-              result = self.run(&n);
-            }
-            self.stack.pop();
-          },
-          None => (),
+        let in_args = match children.first() {
+          Some(Node::FunctionArguments{children}) => children,
+          _ => children,
         };
-        result
+        self.call(name, in_args)
       },
       Node::FunctionDefine{children} => {
         
         let (head, tail) = children.split_at(1);
-        match &head[0] {
-          Node::Identifier{value} => {
-            self.functions.insert(value.to_string(), tail.to_vec());
-          },
-          _ => (),
+        if let Node::Identifier{value} = &head[0] {
+          self.functions.insert(value.to_string(), tail.into());
         }
         Ok(Value::Bool(true))
       },
       Node::FunctionReturn{children} => {
-        self.run(&children[0])
-      },
-      Node::Identifier{value} => {
-        let last = self.stack.len() - 1;
-        match self.stack[last].get(value) {
-          Some(id_value) => Ok(id_value.clone()),
-          None => Err("Undefined variable"),
+        match children.first() {
+          Some(value) => self.run(value),
+          None => Ok(Value::Nil),
         }
       },
+      Node::Identifier{..} |
+      Node::Local{..} => self.lookup(node),
       Node::Statement{children} => {
         match children[0] {
           Node::VariableDefine{..} |
-          Node::FunctionReturn{..} => {
+          Node::FunctionReturn{..} |
+          Node::Expression{..} => {
             self.run(&children[0])
           },
           _ => Err("Unknown Statement"),
         }
       },
-      Node::VariableDefine{children} => {
-        // Variable name
-        let name: String = match &children[0] {
-          Node::Identifier{value} => value.clone(),
-          _ => "".to_string(),
-        };
-        // Expression result
-        let value = self.run(&children[1])?;
-        let last = self.stack.len() - 1;
-        self.stack[last].insert(name, value.clone());
-        Ok(value)
-      }
+      Node::VariableDefine{children} => self.define_variable(children),
       Node::Expression{children} => {
         match &children[0] {
           Node::MathExpression{..} |
@@ -163,7 +257,9 @@ impl Runtime {
           Node::FunctionCall{..} |
           Node::String{..} |
           Node::Bool{..} |
-          Node::Identifier{..} => {
+          Node::Nil |
+          Node::Identifier{..} |
+          Node::Local{..} => {
             self.run(&children[0])
           },
           _ => Err("Unknown Expression"),
@@ -173,22 +269,364 @@ impl Runtime {
         Ok(Value::Number(*value))
       }
       Node::String{value} => {
-        Ok(Value::String(value.clone()))
+        let value = Value::String(value.clone());
+        self.meter.check_size(&value)?;
+        Ok(value)
       }
       Node::Bool{value} => {
         Ok(Value::Bool(*value))
       }
+      Node::Nil => {
+        Ok(Value::Nil)
+      }
+      Node::Condition{conditions} => {
+        self.run(&conditions[0])
+      }
+      Node::TestEquality{children} => self.binary("==", children, |_, lhs, rhs| Ok(Value::Bool(lhs == rhs))),
+      Node::ConditionExpression{name, children} => self.condition(name, children),
       _ => {
         Err("Unhandled Node")
       },
     }
   }
 
+  // The arms of run are kept small and the work done in these helpers, because in a debug
+  // build every local in run takes stack space on each level of script recursion
+
+  fn define(&mut self, children: &[Node]) -> Result<Value, &'static str> {
+    // The last program's top level statements aren't part of this one
+    if let Some(previous) = self.top_level.take() {
+      if self.functions.get("main").map_or(false, |main| Rc::ptr_eq(main, &previous)) {
+        self.functions.remove("main");
+      }
+    }
+    let mut top_level = vec![];
+    for n in children {
+      match n {
+        Node::FunctionDefine{..} => {
+          self.run(n)?;
+        },
+        Node::Expression{..} |
+        Node::Statement{..} => {
+          top_level.push(n.clone());
+          let statements: Rc<[Node]> = main_body(&top_level).into();
+          self.functions.insert("main".to_string(), statements.clone());
+          self.top_level = Some(statements);
+        },
+        _ => (),
+      }
+    }
+    Ok(Value::Bool(true))
+  }
+
+  fn binary(&mut self, op: &str, children: &[Node], f: fn(&str, Value, Value) -> Result<Value, &'static str>) -> Result<Value, &'static str> {
+    let lhs = self.run(&children[0])?;
+    let rhs = self.run(&children[1])?;
+    f(op, lhs, rhs)
+  }
+
+  // A variable that isn't bound in the current function is looked for among the globals
+  fn lookup(&self, node: &Node) -> Result<Value, &'static str> {
+    let frame = &self.stack[self.stack.len() - 1];
+    let value = match node {
+      Node::Identifier{value} => frame.variables.get(value).or_else(|| self.globals.get(value)),
+      Node::Local{value, slot} => frame.slots.get(*slot).and_then(Option::as_ref).or_else(|| self.globals.get(value)),
+      _ => None,
+    };
+    value.cloned().ok_or("Undefined variable")
+  }
+
+  fn define_variable(&mut self, children: &[Node]) -> Result<Value, &'static str> {
+    // Expression result
+    let value = self.run(&children[1])?;
+    if let Some(Node::TypeAnnotation{value: annotation}) = children.get(2) {
+      if !has_type(&value, annotation) {
+        return Err("Variable does not match its type");
+      }
+    }
+    let last = self.stack.len() - 1;
+    match &children[0] {
+      Node::Identifier{value: name} |
+      Node::Local{value: name, ..} if self.stack[last].global => {
+        self.globals.insert(name.clone(), value.clone());
+      },
+      target => self.stack[last].bind(target, value.clone()),
+    }
+    Ok(value)
+  }
+
+  fn condition(&mut self, name: &str, children: &[Node]) -> Result<Value, &'static str> {
+    match name {
+      "!" => Ok(Value::Bool(!self.test(&children[0])?)),
+      "&&" | "&" => Ok(Value::Bool(self.test(&children[0])? && self.test(&children[1])?)),
+      "||" | "|" => Ok(Value::Bool(self.test(&children[0])? || self.test(&children[1])?)),
+      "!=" => Ok(Value::Bool(self.run(&children[0])? != self.run(&children[1])?)),
+      _ => self.binary(name, children, compare),
+    }
+  }
+
+  // Looks up a function and evaluates the arguments into the frame it will run in
+  fn prepare(&mut self, name: &str, in_args: &[Node]) -> Result<(Rc<[Node]>, Frame), &'static str> {
+    let statements = match self.functions.get(name) {
+      Some(statements) => statements.clone(),
+      None => return Err("Undefined function"),
+    };
+    let mut frame = Frame::default();
+    let params = parameters(&statements);
+    if in_args.len() != params.len() {
+      return Err("Wrong number of arguments");
+    }
+    for (param, arg) in params.iter().zip(in_args) {
+      let result = self.run(arg)?;
+      frame.bind_param(param, result)?;
+    }
+    Ok((statements, frame))
+  }
+
+  // Script functions are looked up first, so registering a host function never changes
+  // what an existing script does. Builtins come last, so the host can replace them.
+  fn host_function(&self, name: &str) -> Option<Host> {
+    if self.functions.contains_key(name) {
+      return None;
+    }
+    if let Some(host) = self.host.get(name) {
+      return Some(host.clone());
+    }
+    builtin(name).map(|(_, builtin)| {
+      let output = self.output.clone();
+      Host{arity: builtin.params.len(), function: Rc::new(move |args| (builtin.function)(&output, args))}
+    })
+  }
+
+  fn call_host(&mut self, host: &Host, args: &[Value]) -> Result<Value, RuntimeError> {
+    if args.len() != host.arity {
+      return Err("Wrong number of arguments");
+    }
+    let value = (host.function)(args)?;
+    self.meter.check_size(&value)?;
+    Ok(value)
+  }
+
+  fn call(&mut self, name: &str, in_args: &[Node]) -> Result<Value, &'static str> {
+    if let Some(host) = self.host_function(name) {
+      if in_args.len() != host.arity {
+        return Err("Wrong number of arguments");
+      }
+      let args = in_args.iter().map(|arg| self.run(arg)).collect::<Result<Vec<Value>, RuntimeError>>()?;
+      return self.call_host(&host, &args);
+    }
+    let (statements, frame) = self.prepare(name, in_args)?;
+    self.enter(statements, frame)
+  }
+
+  // Calls a function with arguments that are already values, as the host does
+  pub(crate) fn call_values(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+    if let Some(host) = self.host_function(name) {
+      return self.call_host(&host, args);
+    }
+    let statements = match self.functions.get(name) {
+      Some(statements) => statements.clone(),
+      None => return Err("Undefined function"),
+    };
+    let mut frame = Frame::default();
+    let params = parameters(&statements);
+    if args.len() != params.len() {
+      return Err("Wrong number of arguments");
+    }
+    for (param, arg) in params.iter().zip(args) {
+      frame.bind_param(param, arg.clone())?;
+    }
+    self.enter(statements, frame)
+  }
+
+  // Runs a function, then any function it tail calls, without growing the Rust stack
+  fn enter(&mut self, mut statements: Rc<[Node]>, mut frame: Frame) -> Result<Value, RuntimeError> {
+    // The return annotations of every function in the chain of tail calls, each once. The
+    // last function returns for all of them.
+    let mut returns: Vec<String> = vec![];
+    loop {
+      if self.stack.len() >= self.meter.limits.max_depth {
+        return Err("Stack overflow");
+      }
+      let annotation = statements.iter().take(2).find_map(|n| match n {
+        Node::TypeAnnotation{value} => Some(value.clone()),
+        _ => None,
+      });
+      if let Some(annotation) = annotation {
+        if !returns.contains(&annotation) {
+          returns.push(annotation);
+        }
+      }
+      frame.global = self.top_level.as_ref().map_or(false, |top_level| Rc::ptr_eq(top_level, &statements));
+      self.stack.push(frame);
+      let result = self.run_block(&statements);
+      self.stack.pop();
+      // A function that never reaches a return evaluates to nil
+      let result = match result? {
+        Some(Flow::TailCall(next, next_frame)) => {
+          statements = next;
+          frame = next_frame;
+          continue;
+        },
+        Some(Flow::Return(value)) => value,
+        None => Value::Nil,
+      };
+      return if returns.iter().all(|annotation| has_type(&result, annotation)) {
+        Ok(result)
+      } else {
+        Err("Return value does not match its type")
+      };
+    }
+  }
+
+  // Runs statements in order. As soon as a return executes, however deeply it is nested
+  // in conditionals and loops, it comes back as Some and nothing else runs.
+  fn run_block(&mut self, statements: &[Node]) -> Result<Option<Flow>, &'static str> {
+    for n in statements {
+      match n {
+        Node::FunctionArguments{..} |
+        Node::TypeAnnotation{..} => (),
+        Node::FunctionReturn{..} |
+        Node::Statement{..} if is_return(n) => {
+          if let Some((name, in_args)) = tail_call(n) {
+            if self.functions.contains_key(name) {
+              let (statements, frame) = self.prepare(name, in_args)?;
+              return Ok(Some(Flow::TailCall(statements, frame)));
+            }
+          }
+          return self.run(n).map(|value| Some(Flow::Return(value)));
+        },
+        Node::If{condition, children} => {
+          // With no matching condition we fall through to the else body, if there is one
+          let mut branch = children.get(condition.len());
+          for (ix, c) in condition.iter().enumerate() {
+            if self.test(c)? {
+              branch = children.get(ix);
+              break;
+            }
+          }
+          if let Some(Node::FunctionStatements{children}) = branch {
+            if let Some(flow) = self.run_block(children)? {
+              return Ok(Some(flow));
+            }
+          }
+        },
+        Node::While{condition, children} => {
+          while self.test(&condition[0])? {
+            if let Some(flow) = self.run_block(children)? {
+              return Ok(Some(flow));
+            }
+          }
+        },
+        _ => {
+          self.run(n)?;
+        },
+      }
+    }
+    Ok(None)
+  }
+
+  fn test(&mut self, node: &Node) -> Result<bool, &'static str> {
+    match self.run(node)? {
+      Value::Bool(value) => Ok(value),
+      _ => Err("Condition is not a Bool"),
+    }
+  }
+
+}
+
+// A function's parameters, which come first in its statements when it has any
+fn parameters(statements: &[Node]) -> &[Node] {
+  match statements.first() {
+    Some(Node::FunctionArguments{children}) => children,
+    _ => &[],
+  }
+}
+
+// The body of main for a program's top level statements. They run in order and the last
+// one's value is returned.
+pub(crate) fn main_body(top_level: &[Node]) -> Vec<Node> {
+  let mut body = top_level.to_vec();
+  if let Some(last) = body.pop() {
+    body.push(Node::FunctionReturn{children: vec![last]});
+  }
+  body
+}
+
+// True for a return statement, which ends the enclosing function
+pub(crate) fn is_return(node: &Node) -> bool {
+  match node {
+    Node::FunctionReturn{..} => true,
+    Node::Statement{children} => matches!(children.first(), Some(Node::FunctionReturn{..})),
+    _ => false,
+  }
+}
+
+// The arithmetic operators, shared by the tree walker and the virtual machine. Overflow
+// and division by zero are errors rather than panics.
+pub(crate) fn math(op: &str, lhs: Value, rhs: Value) -> Result<Value, &'static str> {
+  match (lhs, rhs) {
+    (Value::Number(lhs), Value::Number(rhs)) => {
+      let result = match op {
+        "+" => lhs.checked_add(rhs),
+        "-" => lhs.checked_sub(rhs),
+        "*" => lhs.checked_mul(rhs),
+        "/" if rhs == 0 => return Err("Division by zero"),
+        "/" => lhs.checked_div(rhs),
+        // A negative power is 1
+        "^" => if rhs < 0 { Some(1) } else { lhs.checked_pow(rhs as u32) },
+        _ => return Err("Undefined operator"),
+      };
+      result.map(Value::Number).ok_or("Integer overflow")
+    }
+    _ => Err("Cannot do math on String or Bool"),
+  }
+}
+
+// The ordering operators, shared by the tree walker and the virtual machine
+pub(crate) fn compare(op: &str, lhs: Value, rhs: Value) -> Result<Value, &'static str> {
+  match (lhs, rhs) {
+    (Value::Number(lhs), Value::Number(rhs)) => {
+      match op {
+        "<" => Ok(Value::Bool(lhs < rhs)),
+        ">" => Ok(Value::Bool(lhs > rhs)),
+        "<=" => Ok(Value::Bool(lhs <= rhs)),
+        ">=" => Ok(Value::Bool(lhs >= rhs)),
+        _ => Err("Undefined operator"),
+      }
+    }
+    _ => Err("Cannot compare String or Bool"),
+  }
+}
+
+// The call a return statement returns the value of directly, which can run as a tail call
+pub(crate) fn tail_call(node: &Node) -> Option<(&str, &[Node])> {
+  match node {
+    Node::Statement{children} |
+    Node::FunctionReturn{children} |
+    Node::Expression{children} => tail_call(children.first()?),
+    Node::FunctionCall{name, children} => match children.first() {
+      Some(Node::FunctionArguments{children}) => Some((name, children)),
+      _ => Some((name, children)),
+    },
+    _ => None,
+  }
+}
+
+// Annotations are nullable, so nil is accepted for any type
+pub(crate) fn has_type(value: &Value, annotation: &str) -> bool {
+  matches!((value, annotation),
+    (Value::Nil, _) |
+    (Value::Number(_), "int") |
+    (Value::Bool(_), "bool") |
+    (Value::String(_), "string"))
 }
 
 pub fn run(node: &Node) -> Result<Value, &'static str> {
-  let mut runtime = Runtime::new();
-  runtime.run(node);
-  let start_main = Node::FunctionCall{name: "main".to_string(), children: vec![]};
-  runtime.run(&start_main)
+  run_with_limits(node, &RunLimits::default())
+}
+
+// Runs a program, stopping with an error as soon as it goes over one of the limits
+pub fn run_with_limits(node: &Node, limits: &RunLimits) -> Result<Value, &'static str> {
+  Interpreter::new().limits(*limits).run(node)
 }
\ No newline at end of file
@@ -0,0 +1,286 @@
+use crate::lexer::next_token;
+use crate::parser::Node;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+// What a piece of source text is. Whitespace and comments are trivia: they don't change
+// what the program means, but they are kept so the source can be written back out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+  Whitespace,
+  Comment,
+  Keyword,
+  Identifier,
+  Number,
+  String,
+  Operator,
+  Punctuation,
+  // Text the language doesn't allow, which only the lexer produces
+  Invalid,
+}
+
+impl TokenKind {
+  pub fn is_trivia(self) -> bool {
+    matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+  }
+}
+
+// A piece of source text and the byte offset where it started in the parsed source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+  pub kind: TokenKind,
+  pub text: String,
+  pub offset: usize,
+}
+
+// Syntax nodes are named after the Node each one was parsed as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+  Program,
+  Statement,
+  FunctionReturn,
+  FunctionDefine,
+  FunctionArguments,
+  FunctionStatements,
+  Expression,
+  MathExpression,
+  FunctionCall,
+  VariableDefine,
+  Number,
+  Bool,
+  Identifier,
+  String,
+  Nil,
+  TypeAnnotation,
+  If,
+  While,
+  Condition,
+  ConditionExpression,
+  TestEquality,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+  Node(SyntaxNode),
+  Token(Token),
+}
+
+// The concrete syntax tree. Every character of the source is in exactly one token, in
+// order, so writing the tokens back out gives the source again. Trivia between two nodes
+// belongs to the node around them: a comment between statements is a child of the block,
+// not of either statement. Parentheses belong to the node around what they group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+  pub kind: SyntaxKind,
+  pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+  // Every token under this node, trivia included, in source order
+  pub fn tokens(&self) -> Vec<&Token> {
+    let mut tokens = vec![];
+    for child in &self.children {
+      match child {
+        SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+        SyntaxElement::Token(token) => tokens.push(token),
+      }
+    }
+    tokens
+  }
+
+  // The same, for rewriting. Offsets aren't updated, so they keep pointing into the
+  // original source.
+  pub fn tokens_mut(&mut self) -> Vec<&mut Token> {
+    let mut tokens = vec![];
+    for child in &mut self.children {
+      match child {
+        SyntaxElement::Node(node) => tokens.extend(node.tokens_mut()),
+        SyntaxElement::Token(token) => tokens.push(token),
+      }
+    }
+    tokens
+  }
+
+  // The child nodes, without the tokens between them
+  pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+    self.children.iter().filter_map(|child| match child {
+      SyntaxElement::Node(node) => Some(node),
+      SyntaxElement::Token(_) => None,
+    })
+  }
+
+  // Where the node's text is in the parsed source
+  pub fn span(&self) -> Range<usize> {
+    let tokens = self.tokens();
+    match (tokens.first(), tokens.last()) {
+      (Some(first), Some(last)) => first.offset..last.offset + last.text.len(),
+      _ => 0..0,
+    }
+  }
+}
+
+impl fmt::Display for SyntaxNode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for token in self.tokens() {
+      f.write_str(&token.text)?;
+    }
+    Ok(())
+  }
+}
+
+// Where each node of a tree is in the source, found by walking it alongside the syntax
+// tree made for it, which has a node for each of its nodes. Nodes are told apart by
+// address, so the table borrows the tree it was made for.
+pub struct Spans<'a> {
+  spans: HashMap<*const Node, Range<usize>>,
+  tree: PhantomData<&'a Node>,
+}
+
+impl<'a> Spans<'a> {
+  pub fn new(ast: &'a Node, syntax: &SyntaxNode) -> Spans<'a> {
+    let mut spans = Spans{spans: HashMap::new(), tree: PhantomData};
+    spans.insert(ast, syntax);
+    spans
+  }
+
+  fn insert(&mut self, node: &Node, syntax: &SyntaxNode) {
+    self.spans.insert(node as *const Node, syntax.span());
+    for (child, syntax) in in_source_order(node).into_iter().zip(syntax.nodes()) {
+      self.insert(child, syntax);
+    }
+  }
+
+  // Where node is, or an empty span at the start for one that isn't in the tree
+  pub fn get(&self, node: &Node) -> Range<usize> {
+    self.spans.get(&(node as *const Node)).cloned().unwrap_or(0..0)
+  }
+}
+
+// A node's children in the order they are written
+pub(crate) fn in_source_order(node: &Node) -> Vec<&Node> {
+  match node {
+    // The annotation is kept last but written before the value
+    Node::VariableDefine{children} => children.iter().take(1).chain(children.get(2)).chain(children.get(1)).collect(),
+    // Each condition is written before its body
+    Node::If{condition, children} => {
+      let mut nodes = vec![];
+      for (ix, body) in children.iter().enumerate() {
+        nodes.extend(condition.get(ix));
+        nodes.push(body);
+      }
+      nodes
+    },
+    Node::While{condition, children} => condition.iter().chain(children).collect(),
+    Node::Condition{conditions: children} |
+    Node::Program{children} |
+    Node::Statement{children} |
+    Node::FunctionReturn{children} |
+    Node::FunctionDefine{children} |
+    Node::FunctionArguments{children} |
+    Node::FunctionStatements{children} |
+    Node::Expression{children} |
+    Node::MathExpression{children, ..} |
+    Node::FunctionCall{children, ..} |
+    Node::ConditionExpression{children, ..} |
+    Node::TestEquality{children} => children.iter().collect(),
+    Node::Number{..} |
+    Node::Bool{..} |
+    Node::Identifier{..} |
+    Node::Local{..} |
+    Node::String{..} |
+    Node::Nil |
+    Node::TypeAnnotation{..} => vec![],
+  }
+}
+
+// What the parser read a node from: the tokens it took and the syntax nodes around them,
+// in order. Trivia isn't in the parser's tokens, so it is put back in from the source
+// when the tree is made.
+#[derive(Debug, Default)]
+pub struct Syntax(Vec<Piece>);
+
+#[derive(Debug)]
+enum Piece {
+  Token(TokenKind, Range<usize>),
+  Node(SyntaxKind, Vec<Piece>),
+}
+
+impl From<&Token> for Syntax {
+  fn from(token: &Token) -> Syntax {
+    Syntax::token(token.kind, token)
+  }
+}
+
+impl Extend<Syntax> for Syntax {
+  fn extend<I: IntoIterator<Item = Syntax>>(&mut self, iter: I) {
+    for syntax in iter {
+      self.0.extend(syntax.0);
+    }
+  }
+}
+
+impl Syntax {
+
+  // A token read as another kind than the lexer gave it
+  pub(crate) fn token(kind: TokenKind, token: &Token) -> Syntax {
+    Syntax(vec![Piece::Token(kind, token.offset..token.offset + token.text.len())])
+  }
+
+  pub(crate) fn then<S: Into<Syntax>>(mut self, next: S) -> Syntax {
+    self.0.extend(next.into().0);
+    self
+  }
+
+  pub(crate) fn node(self, kind: SyntaxKind) -> Syntax {
+    Syntax(vec![Piece::Node(kind, self.0)])
+  }
+
+  // The tree of a program read from source. Whatever is between the tokens is lexed into
+  // the node that is open there, which is the program for a stretch that didn't parse.
+  pub(crate) fn tree(self, source: &str) -> SyntaxNode {
+    let mut root = SyntaxNode{kind: SyntaxKind::Program, children: vec![]};
+    let mut pos = 0;
+    fill(source, &mut pos, &mut root, self.0);
+    lex(source, &mut pos, source.len(), &mut root);
+    root
+  }
+}
+
+fn fill(source: &str, pos: &mut usize, parent: &mut SyntaxNode, pieces: Vec<Piece>) {
+  for piece in pieces {
+    match piece {
+      Piece::Token(kind, range) => {
+        lex(source, pos, range.start, parent);
+        let token = Token{kind, text: source[range.clone()].to_string(), offset: range.start};
+        parent.children.push(SyntaxElement::Token(token));
+        *pos = range.end;
+      },
+      Piece::Node(kind, pieces) => {
+        // Trivia before a node belongs to the node around it
+        lex(source, pos, start(&pieces).unwrap_or(*pos), parent);
+        let mut node = SyntaxNode{kind, children: vec![]};
+        fill(source, pos, &mut node, pieces);
+        parent.children.push(SyntaxElement::Node(node));
+      },
+    }
+  }
+}
+
+fn start(pieces: &[Piece]) -> Option<usize> {
+  match pieces.first()? {
+    Piece::Token(_, range) => Some(range.start),
+    Piece::Node(_, pieces) => start(pieces),
+  }
+}
+
+// Takes the source up to end as the lexer reads it
+fn lex(source: &str, pos: &mut usize, end: usize, parent: &mut SyntaxNode) {
+  while *pos < end {
+    let (kind, len) = next_token(&source[*pos..end]);
+    let token = Token{kind, text: source[*pos..*pos + len].to_string(), offset: *pos};
+    parent.children.push(SyntaxElement::Token(token));
+    *pos += len;
+  }
+}
@@ -0,0 +1,173 @@
+use crate::builtins::BUILTINS;
+use crate::bytecode::{Annotation, Bytecode, Instruction};
+use crate::output::Output;
+use crate::runtime::{compare, has_type, math, Meter, RunLimits, Value};
+use std::collections::HashMap;
+
+struct Frame {
+  function: usize,
+  ip: usize,
+  slots: Vec<Option<Value>>,
+  // The return annotations of the functions that tail called this one, which it returns for
+  returns: Vec<Annotation>,
+}
+
+fn pop(stack: &mut Vec<Value>) -> Value {
+  stack.pop().expect("stack underflow")
+}
+
+fn pop_bool(stack: &mut Vec<Value>) -> Result<bool, &'static str> {
+  match pop(stack) {
+    Value::Bool(value) => Ok(value),
+    _ => Err("Condition is not a Bool"),
+  }
+}
+
+// Pops the right then left operand and pushes the result of the operator
+fn binary(stack: &mut Vec<Value>, op: &str, f: fn(&str, Value, Value) -> Result<Value, &'static str>) -> Result<(), &'static str> {
+  let rhs = pop(stack);
+  let lhs = pop(stack);
+  stack.push(f(op, lhs, rhs)?);
+  Ok(())
+}
+
+// Runs compiled bytecode from main. Calls push frames onto a heap allocated stack,
+// so recursion in the script does not recurse in Rust.
+pub fn execute(bytecode: &Bytecode) -> Result<Value, &'static str> {
+  execute_with_limits(bytecode, &RunLimits::default())
+}
+
+// Runs compiled bytecode, stopping with the same errors as runtime::run_with_limits
+pub fn execute_with_limits(bytecode: &Bytecode, limits: &RunLimits) -> Result<Value, &'static str> {
+  execute_with_output(bytecode, limits, &Output::stdout())
+}
+
+// Runs compiled bytecode with print and println writing to output
+pub fn execute_with_output(bytecode: &Bytecode, limits: &RunLimits, output: &Output) -> Result<Value, &'static str> {
+  execute_with_globals(bytecode, limits, output, HashMap::new())
+}
+
+// Runs compiled bytecode with globals set up front, as Interpreter::set_global does
+pub fn execute_with_globals(bytecode: &Bytecode, limits: &RunLimits, output: &Output, mut globals: HashMap<String, Value>) -> Result<Value, &'static str> {
+  let mut meter = Meter::new(limits);
+  let main = match bytecode.main {
+    Some(main) => main,
+    None => return Err("Undefined function"),
+  };
+  if !bytecode.functions[main].params.is_empty() {
+    return Err("Wrong number of arguments");
+  }
+  if limits.max_depth == 0 {
+    return Err("Stack overflow");
+  }
+  let mut frames = vec![Frame{function: main, ip: 0, slots: vec![None; bytecode.functions[main].slots.len()], returns: vec![]}];
+  let mut stack: Vec<Value> = vec![];
+  loop {
+    meter.step()?;
+    let frame = frames.last_mut().expect("no frame");
+    let function = &bytecode.functions[frame.function];
+    let instruction = &function.code[frame.ip];
+    frame.ip += 1;
+    match instruction {
+      Instruction::Constant(index) => {
+        let value = bytecode.constants[*index].clone();
+        meter.check_size(&value)?;
+        stack.push(value);
+      },
+      Instruction::Nil => stack.push(Value::Nil),
+      Instruction::Load(slot) => {
+        match frame.slots[*slot].as_ref().or_else(|| globals.get(&function.slots[*slot])) {
+          Some(value) => stack.push(value.clone()),
+          None => return Err("Undefined variable"),
+        }
+      },
+      Instruction::Store(slot) => frame.slots[*slot] = Some(pop(&mut stack)),
+      Instruction::StoreGlobal(slot) => {
+        globals.insert(function.slots[*slot].clone(), pop(&mut stack));
+      },
+      Instruction::Pop => {
+        pop(&mut stack);
+      },
+      Instruction::Dup => {
+        let top = stack.last().expect("stack underflow").clone();
+        stack.push(top);
+      },
+      Instruction::Add => binary(&mut stack, "+", math)?,
+      Instruction::Subtract => binary(&mut stack, "-", math)?,
+      Instruction::Multiply => binary(&mut stack, "*", math)?,
+      Instruction::Divide => binary(&mut stack, "/", math)?,
+      Instruction::Power => binary(&mut stack, "^", math)?,
+      Instruction::Less => binary(&mut stack, "<", compare)?,
+      Instruction::Greater => binary(&mut stack, ">", compare)?,
+      Instruction::LessEqual => binary(&mut stack, "<=", compare)?,
+      Instruction::GreaterEqual => binary(&mut stack, ">=", compare)?,
+      Instruction::Equal | Instruction::NotEqual => {
+        let rhs = pop(&mut stack);
+        let lhs = pop(&mut stack);
+        let equal = lhs == rhs;
+        stack.push(Value::Bool(if *instruction == Instruction::Equal { equal } else { !equal }));
+      },
+      Instruction::Not => {
+        let value = pop_bool(&mut stack)?;
+        stack.push(Value::Bool(!value));
+      },
+      Instruction::Jump(target) => frame.ip = *target,
+      Instruction::JumpIfFalse(target) => {
+        if !pop_bool(&mut stack)? {
+          frame.ip = *target;
+        }
+      },
+      Instruction::Call(index) |
+      Instruction::TailCall(index) => {
+        let function = &bytecode.functions[*index];
+        let args = stack.split_off(stack.len() - function.params.len());
+        let mut slots = vec![None; function.slots.len()];
+        for (slot, arg) in function.params.iter().zip(args) {
+          slots[*slot] = Some(arg);
+        }
+        let mut returns = vec![];
+        if let Instruction::TailCall(_) = instruction {
+          returns = frames.pop().expect("no frame").returns;
+        } else if frames.len() >= limits.max_depth {
+          return Err("Stack overflow");
+        }
+        frames.push(Frame{function: *index, ip: 0, slots, returns});
+      },
+      Instruction::CallBuiltin(index) => {
+        let builtin = &BUILTINS[*index];
+        let args = stack.split_off(stack.len() - builtin.params.len());
+        let value = (builtin.function)(output, &args)?;
+        meter.check_size(&value)?;
+        stack.push(value);
+      },
+      Instruction::Return => {
+        let top = stack.last().expect("stack underflow");
+        if frame.returns.iter().any(|annotation| !has_type(top, annotation.name())) {
+          return Err("Return value does not match its type");
+        }
+        frames.pop();
+        if frames.is_empty() {
+          return Ok(pop(&mut stack));
+        }
+      },
+      Instruction::CheckArgument(annotation) |
+      Instruction::CheckVariable(annotation) |
+      Instruction::CheckReturn(annotation) => {
+        let top = stack.last().expect("stack underflow");
+        if !has_type(top, annotation.name()) {
+          return Err(match instruction {
+            Instruction::CheckArgument(_) => "Argument does not match its type",
+            Instruction::CheckVariable(_) => "Variable does not match its type",
+            _ => "Return value does not match its type",
+          });
+        }
+      },
+      Instruction::ExpectReturn(annotation) => {
+        if !frame.returns.contains(annotation) {
+          frame.returns.push(*annotation);
+        }
+      },
+      Instruction::Fail(message) => return Err(message),
+    }
+  }
+}
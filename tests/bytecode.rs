@@ -0,0 +1,148 @@
+extern crate cse262_project;
+
+use cse262_project::{program, parse_with_syntax, compile, compile_with_lines, execute, Annotation, Bytecode, Function, Instruction, LoadError, Spans, Value};
+
+const PROGRAM: &str = r#"fn main() -> int {
+  let s: string = "hello world";
+  if s == "hello world" && !false {
+    return add(1,2);
+  }
+  return missing();
+}
+fn add(a: int, b: int) -> int {
+  return a + b;
+}"#;
+
+fn compiled() -> Bytecode {
+  let (input, p) = program(PROGRAM).unwrap();
+  assert_eq!(input, "");
+  compile(&p)
+}
+
+// Rewrites the checksum so a deliberately altered payload gets past the CRC check
+fn reseal(bytes: &mut [u8]) {
+  let crc = cse262_project::crc32(&bytes[16..]);
+  bytes[12..16].copy_from_slice(&crc.to_le_bytes());
+}
+
+#[test]
+fn round_trip() {
+  let bytecode = compiled();
+  // main's annotation covers the call to add it returns
+  assert!(bytecode.functions[0].code.contains(&Instruction::ExpectReturn(Annotation::Int)));
+  let loaded = Bytecode::from_bytes(&bytecode.to_bytes()).unwrap();
+  assert_eq!(loaded, bytecode);
+  assert_eq!(execute(&loaded), Ok(Value::Number(3)));
+}
+
+// The lines each function's code came from, in order
+fn lines(source: &str) -> Vec<(String, Vec<u32>)> {
+  let (ast, tree, errors) = parse_with_syntax(source);
+  assert_eq!(errors, vec![]);
+  let bytecode = compile_with_lines(&ast, &Spans::new(&ast, &tree), source);
+  let loaded = Bytecode::from_bytes(&bytecode.to_bytes()).unwrap();
+  assert_eq!(loaded, bytecode);
+  bytecode.functions.iter().enumerate().map(|(ix, function)| {
+    let entries = bytecode.lines.iter().filter(|l| l.function == ix);
+    (function.name.clone(), entries.map(|l| l.line).collect())
+  }).collect()
+}
+
+#[test]
+fn round_trip_with_lines() {
+  assert_eq!(lines(PROGRAM), vec![("main".to_string(), vec![2, 3, 4, 6]), ("add".to_string(), vec![9])]);
+  let source = "fn add (a) {\n  let b = a;\n  while b < 3 {\n    let b = b + 1;\n  }\n  return b;\n}\nlet x = add(1);\n\nprintln(x)\n";
+  assert_eq!(lines(source), vec![("add".to_string(), vec![2, 3, 4, 6]), ("main".to_string(), vec![8, 10])]);
+}
+
+#[test]
+fn not_bytecode() {
+  assert_eq!(Bytecode::from_bytes(b""), Err(LoadError::BadMagic));
+  assert_eq!(Bytecode::from_bytes(PROGRAM.as_bytes()), Err(LoadError::BadMagic));
+}
+
+#[test]
+fn truncated() {
+  let bytes = compiled().to_bytes();
+  for len in 4..bytes.len() {
+    assert_eq!(Bytecode::from_bytes(&bytes[..len]), Err(LoadError::Truncated), "length {}", len);
+  }
+}
+
+#[test]
+fn checksum_mismatch() {
+  let mut bytes = compiled().to_bytes();
+  let last = bytes.len() - 1;
+  bytes[last] ^= 0xFF;
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::ChecksumMismatch));
+}
+
+#[test]
+fn unsupported_version() {
+  let mut bytes = compiled().to_bytes();
+  bytes[4] = 99;
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::UnsupportedVersion(99)));
+}
+
+#[test]
+fn trailing_data() {
+  let mut bytes = compiled().to_bytes();
+  bytes.push(0);
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::Corrupt("unexpected data after the program")));
+}
+
+#[test]
+fn unknown_opcode() {
+  let mut bytes = compiled().to_bytes();
+  // The first instruction of main follows its name, params, the name of its one slot "s"
+  // and code length
+  let at = bytes.windows(4).position(|w| w == b"main").unwrap() + 4 + 4 + 4 + 4 + 1 + 4;
+  bytes[at] = 200;
+  reseal(&mut bytes);
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::Corrupt("unknown opcode")));
+}
+
+fn single(code: Vec<Instruction>) -> Bytecode {
+  Bytecode{
+    constants: vec![Value::Number(1)],
+    functions: vec![Function{name: "main".to_string(), params: vec![], slots: vec!["x".to_string()], code}],
+    main: Some(0),
+    lines: vec![],
+  }
+}
+
+#[test]
+fn bad_references() {
+  for code in [
+    vec![Instruction::Constant(1), Instruction::Return],
+    vec![Instruction::Load(1), Instruction::Return],
+    vec![Instruction::Jump(5), Instruction::Return],
+    vec![Instruction::Call(1), Instruction::Return],
+  ] {
+    let bytes = single(code).to_bytes();
+    assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::Corrupt("instruction refers outside the program")));
+  }
+}
+
+#[test]
+fn bad_stack() {
+  let bytes = single(vec![Instruction::Pop, Instruction::Nil, Instruction::Return]).to_bytes();
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::Corrupt("instruction pops an empty stack")));
+  let bytes = single(vec![Instruction::Nil, Instruction::Nil, Instruction::Return]).to_bytes();
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::Corrupt("return leaves values on the stack")));
+  let bytes = single(vec![Instruction::Nil]).to_bytes();
+  assert_eq!(Bytecode::from_bytes(&bytes), Err(LoadError::Corrupt("function does not end with a return")));
+}
+
+#[test]
+fn collection_constants() {
+  let mut map = std::collections::HashMap::new();
+  map.insert("b".to_string(), Value::Nil);
+  map.insert("a".to_string(), Value::List(vec![Value::Number(1), Value::String("x".to_string())]));
+  let mut bytecode = single(vec![Instruction::Constant(0), Instruction::Return]);
+  bytecode.constants = vec![Value::Map(map)];
+  let bytes = bytecode.to_bytes();
+  assert_eq!(bytes, bytecode.clone().to_bytes());
+  assert_eq!(Bytecode::from_bytes(&bytes), Ok(bytecode.clone()));
+  assert_eq!(execute(&bytecode), Ok(bytecode.constants[0].clone()));
+}
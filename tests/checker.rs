@@ -0,0 +1,196 @@
+extern crate cse262_project;
+
+use cse262_project::{program, parse_with_syntax, check, infer_types, Spans, Type, TypeError};
+
+fn type_errors(source: &str) -> Vec<TypeError> {
+  let (p, tree, errors) = parse_with_syntax(source);
+  assert_eq!(errors, vec![]);
+  match check(&p, &Spans::new(&p, &tree)) {
+    Ok(()) => vec![],
+    Err(errors) => errors,
+  }
+}
+
+fn errors(source: &str) -> Vec<String> {
+  type_errors(source).iter().map(TypeError::to_string).collect()
+}
+
+// The text each error is located at
+fn located(source: &str) -> Vec<&str> {
+  type_errors(source).iter().map(|e| &source[e.span.clone()]).collect()
+}
+
+#[test]
+fn well_typed_program() {
+  assert_eq!(errors(r#"fn foo(a,b,c) {
+  let x = a + 1;
+  let y = bar(c - b);
+  return x * y;
+}
+
+fn bar(a) {
+  return a * 3;
+}
+
+fn main() {
+  return foo(1,2,3);
+}"#), Vec::<String>::new());
+}
+
+#[test]
+fn math_on_string() {
+  assert_eq!(errors(r#"let x = "a" * 2;"#), vec!["in function `main`: `*` expects Number but found String"]);
+}
+
+#[test]
+fn math_on_bool() {
+  assert_eq!(errors(r#"true + 1"#), vec!["in function `main`: `+` expects Number but found Bool"]);
+}
+
+#[test]
+fn reports_every_error() {
+  assert_eq!(errors(r#"fn main() {
+  let x = "a" * 2;
+  let y = true + 1;
+  return x;
+}"#), vec![
+    "in function `main`: `*` expects Number but found String",
+    "in function `main`: `+` expects Number but found Bool",
+  ]);
+}
+
+#[test]
+fn errors_are_located() {
+  let source = r#"fn main() {
+  let x = "a" * (2 + 3);
+  let y: int = true;
+  if x {
+    return foo(x, 1);
+  }
+  return foo("b");
+}
+fn foo(a) -> int {
+  return a;
+}"#;
+  assert_eq!(located(source), vec![r#""a""#, "true", "x", "foo(x, 1)", r#""b""#]);
+  assert_eq!(type_errors(r#"let x: bool = 1;"#)[0].span, 14..15);
+}
+
+#[test]
+fn infers_variable_types() {
+  assert_eq!(errors(r#"fn main() {
+  let s = "hello";
+  let t = s;
+  return t - 1;
+}"#), vec!["in function `main`: `-` expects Number but found String"]);
+}
+
+#[test]
+fn infers_parameter_types_from_use() {
+  assert_eq!(errors(r#"fn main(){return foo("a");} fn foo(a){return a+1;}"#),
+    vec!["in function `main`: argument 1 of `foo` expects Number but found String"]);
+}
+
+#[test]
+fn infers_return_types() {
+  assert_eq!(errors(r#"fn main(){return foo() + 1;} fn foo(){return true;}"#),
+    vec!["in function `main`: `+` expects Number but found Bool"]);
+}
+
+#[test]
+fn conflicting_returns() {
+  assert_eq!(errors(r#"fn main() {
+  if true {
+    return 1;
+  }
+  return "one";
+}"#), vec!["in function `main`: return expects Number but found String"]);
+}
+
+#[test]
+fn nil_is_allowed_anywhere() {
+  assert_eq!(errors(r#"fn main() {
+  if true {
+    return 1;
+  }
+  return;
+}"#), Vec::<String>::new());
+}
+
+#[test]
+fn only_nil_fits_nil() {
+  assert_eq!(errors(r#"fn f() -> nil { return 1; } fn main() { return f(); }"#),
+    vec!["in function `f`: return expects Nil but found Number"]);
+  assert_eq!(errors(r#"let x: nil = "a";"#), vec!["in function `main`: annotation expects Nil but found String"]);
+  assert_eq!(errors(r#"fn f() -> nil { return nil; } fn main() { let x: nil = f(); return x; }"#), Vec::<String>::new());
+  // An empty return found first doesn't fix the type of the rest
+  assert_eq!(errors(r#"fn main() {
+  if true {
+    return;
+  }
+  return 1;
+}"#), Vec::<String>::new());
+}
+
+#[test]
+fn condition_must_be_bool() {
+  assert_eq!(errors(r#"fn main() {
+  while 1 + 1 {
+    return 1;
+  }
+}"#), vec!["in function `main`: while condition expects Bool but found Number"]);
+}
+
+#[test]
+fn wrong_argument_count() {
+  assert_eq!(errors(r#"fn main(){return foo(1,2);} fn foo(a){return a;}"#),
+    vec!["in function `main`: `foo` takes 1 arguments but was given 2"]);
+}
+
+#[test]
+fn variable_annotation() {
+  assert_eq!(errors(r#"let x: bool = 1;"#), vec!["in function `main`: annotation expects Bool but found Number"]);
+}
+
+#[test]
+fn parameter_annotations() {
+  assert_eq!(errors(r#"fn main(){return add("a",2);} fn add(a: int, b: int) -> int {return a + b;}"#),
+    vec!["in function `main`: argument 1 of `add` expects Number but found String"]);
+}
+
+#[test]
+fn parameter_annotations_inside_body() {
+  assert_eq!(errors(r#"fn main(){return 1;} fn shout(s: string) {return s * 2;}"#),
+    vec!["in function `shout`: `*` expects Number but found String"]);
+}
+
+#[test]
+fn return_annotation() {
+  assert_eq!(errors(r#"fn main() -> int {return true;}"#), vec!["in function `main`: return expects Number but found Bool"]);
+}
+
+#[test]
+fn builtin_signatures() {
+  assert_eq!(errors(r#"let x: int = to_string(1);"#), vec!["in function `main`: annotation expects Number but found String"]);
+  assert_eq!(errors(r#"fn main(){return abs("a");}"#), vec!["in function `main`: argument 1 of `abs` expects Number but found String"]);
+  assert_eq!(errors(r#"fn main(){return max(1);}"#), vec!["in function `main`: `max` takes 2 arguments but was given 1"]);
+  // Each call to a builtin taking any value is checked on its own
+  assert_eq!(errors(r#"fn main(){println(1); println("a"); return len("abc") + 1;}"#), Vec::<String>::new());
+}
+
+#[test]
+fn inferred_types() {
+  let (_, p) = program(r#"fn greet(name, times) {
+  let n = times * 2;
+  let s = name;
+  return println(s);
+}
+let who = "you";
+greet(who,1);"#).unwrap();
+  let types = infer_types(&p);
+  assert_eq!(types.functions["greet"].0[1], Type::Number);
+  assert_eq!(types.functions["greet"].1, Type::Nil);
+  assert_eq!(types.variables["greet"]["n"], Type::Number);
+  assert_eq!(types.variables["greet"]["s"], Type::String);
+  assert_eq!(types.variables["main"]["who"], Type::String);
+}
@@ -0,0 +1,175 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::{env, fs};
+
+// Writes a script where only this test will look for it
+fn script(name: &str, source: &str) -> PathBuf {
+  let path = env::temp_dir().join(format!("cse262-cli-{}-{}.src", name, std::process::id()));
+  fs::write(&path, source).unwrap();
+  path
+}
+
+fn cse262(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_cse262")).args(args).output().unwrap()
+}
+
+fn repl(input: &str) -> Output {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_cse262")).arg("repl")
+    .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+    .spawn().unwrap();
+  child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+  child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+  String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+  String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn runs_a_file() {
+  let path = script("runs", "fn main() {\n  println(\"hello\");\n  return 6 * 7;\n}\n");
+  for args in [vec!["run"], vec!["run", "--vm"], vec!["run", "--check"]] {
+    let output = cse262(&[&args[..], &[path.to_str().unwrap()]].concat());
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "hello\n42\n");
+  }
+}
+
+#[test]
+fn passes_program_arguments() {
+  let path = script("args", "println(args);\nlen(args)\n");
+  let output = cse262(&["run", path.to_str().unwrap(), "one", "two"]);
+  assert_eq!(stdout(&output), "[one, two]\n2\n");
+  let output = cse262(&["run", "--vm", path.to_str().unwrap()]);
+  assert_eq!(stdout(&output), "[]\n0\n");
+  let path = script("get-args", "let name = get(args, 0);\nprintln(name);\nparse_int(get(args, 1)) * 2\n");
+  for args in [vec!["run"], vec!["run", "--vm"]] {
+    let output = cse262(&[&args[..], &[path.to_str().unwrap(), "world", "21"]].concat());
+    assert_eq!(stdout(&output), "world\n42\n");
+  }
+}
+
+#[test]
+fn runs_compiled_files() {
+  let path = script("compiled", "let n = len(args);\nn * 10\n");
+  let compiled = path.with_extension("bc");
+  let output = cse262(&["run", "--emit", compiled.to_str().unwrap(), path.to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(0));
+  let output = cse262(&["run", compiled.to_str().unwrap(), "a", "b", "c"]);
+  assert_eq!(stdout(&output), "30\n");
+}
+
+#[test]
+fn nil_results_are_not_printed() {
+  let path = script("nil", "println(1);\n");
+  assert_eq!(stdout(&cse262(&["run", path.to_str().unwrap()])), "1\n");
+}
+
+#[test]
+fn exit_codes() {
+  let output = cse262(&[]);
+  assert_eq!(output.status.code(), Some(64));
+  assert!(stderr(&output).starts_with("usage:"));
+  assert_eq!(cse262(&["run", "--fast", "x.src"]).status.code(), Some(64));
+  assert_eq!(cse262(&["run", "/nonexistent/x.src"]).status.code(), Some(66));
+
+  let output = cse262(&["run", script("parse", "let x = 1;\n@ 2\n").to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(65));
+  assert_eq!(stderr(&output), "parse error on line 2: unexpected `@ 2`\n");
+
+  let output = cse262(&["run", "--check", script("types", "let x: int = true;\n").to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(65));
+  assert_eq!(stderr(&output), "type error on line 1 in function `main`: annotation expects Number but found Bool\n");
+
+  let output = cse262(&["run", script("runtime", "println(1);\n1 / 0\n").to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(70));
+  assert_eq!(stdout(&output), "1\n");
+  assert_eq!(stderr(&output), "runtime error: Division by zero\n");
+}
+
+#[test]
+fn keyword_as_name() {
+  let output = cse262(&["run", script("keyword", "let x = 1;\nfn add(a, let) {\n  return a;\n}\n").to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(65));
+  assert_eq!(stderr(&output), "parse error on line 2: `let` is a keyword and can't be used as a name\n");
+}
+
+#[test]
+fn every_parse_error_is_reported() {
+  let output = cse262(&["run", script("errors", "let x = ;\nprintln(1);\nfn f( {\n}\nlet y = @;\n").to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(65));
+  assert_eq!(stdout(&output), "");
+  assert_eq!(stderr(&output), "parse error on line 1: unexpected `let x = ;`\nparse error on line 3: unexpected `fn f( {`\nparse error on line 5: unexpected `let y = @;`\n");
+}
+
+#[test]
+fn repl_keeps_state() {
+  let output = repl("let x = 2;\nfn square(n) {\n  if n < 0 {\n    return 0 - n * n;\n  }\n  return n * n;\n}\nsquare(x) + 1\nprintln(\"done\");\n");
+  assert_eq!(stdout(&output), "2\n5\ndone\n");
+  assert_eq!(stderr(&output), "");
+  assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn repl_reports_errors_and_continues() {
+  let output = repl("1 / 0\n@\nlet y = 3;\ny\n");
+  assert_eq!(stdout(&output), "3\n3\n");
+  assert_eq!(stderr(&output), "runtime error: Division by zero\nparse error on line 1: unexpected `@`\n");
+}
+
+#[test]
+fn repl_commands() {
+  let path = script("load", "fn double(n) {\n  return n * 2;\n}\nlet z = double(4);\n");
+  let output = repl(&format!(":load {}\nz + double(1)\n:reset\nz\n:ast nil\n:nope\n:quit\n1\n", path.display()));
+  assert_eq!(stdout(&output), "8\n10\nProgram\n  Expression\n    Nil\n");
+  assert_eq!(stderr(&output), "runtime error: Undefined variable\nunknown command :nope, try :help\n");
+}
+
+#[test]
+fn dumps_the_tree() {
+  let path = script("ast", "let x = 1;\n");
+  let output = cse262(&["ast", path.to_str().unwrap()]);
+  assert_eq!(stdout(&output), "Program\n  Statement\n    VariableDefine\n      Identifier \"x\"\n      Expression\n        Number 1\n");
+  let output = cse262(&["ast", "--json", path.to_str().unwrap()]);
+  assert!(stdout(&output).starts_with("{\n  \"type\": \"Program\",\n  \"children\": [\n"));
+  assert_eq!(cse262(&["ast", script("ast-bad", "@").to_str().unwrap()]).status.code(), Some(65));
+}
+
+#[test]
+fn formats_files() {
+  let path = script("fmt", "let x=1+2;\nx");
+  let output = cse262(&["fmt", "--check", path.to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(1));
+  assert_eq!(stdout(&output), format!("{} is not formatted\n", path.display()));
+  assert_eq!(cse262(&["fmt", path.to_str().unwrap()]).status.code(), Some(0));
+  assert_eq!(fs::read_to_string(&path).unwrap(), "let x = 1 + 2;\nx\n");
+  let output = cse262(&["fmt", "--check", path.to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(stdout(&output), "");
+  assert_eq!(cse262(&["fmt", script("fmt-bad", "@").to_str().unwrap()]).status.code(), Some(65));
+}
+
+#[test]
+fn formatting_keeps_comments() {
+  let path = script("fmt-comments", "// setup\nlet x=1; // one\n\n\nx\n");
+  assert_eq!(cse262(&["fmt", path.to_str().unwrap()]).status.code(), Some(0));
+  assert_eq!(fs::read_to_string(&path).unwrap(), "// setup\nlet x = 1; // one\n\nx\n");
+  // A comment inside an if used as a value has nowhere to go, so the file is left alone
+  let source = "let x=if true {\n  // kept\n  1\n} else {\n  2\n};\n";
+  let path = script("fmt-lost", source);
+  let output = cse262(&["fmt", path.to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(70));
+  assert_eq!(stderr(&output), format!("cannot format {}: comments would be lost\n", path.display()));
+  assert_eq!(fs::read_to_string(&path).unwrap(), source);
+}
+
+#[test]
+fn repl_ignores_braces_in_comments() {
+  let output = repl("let x = 1; // {\nx\n");
+  assert_eq!(stdout(&output), "1\n1\n");
+}
@@ -0,0 +1,8 @@
+use cse262_project::{program, Node};
+
+// Parses a whole program, failing the test if any of it is left over
+pub fn parse(source: &str) -> Node {
+  let (input, p) = program(source).unwrap();
+  assert_eq!(input, "");
+  p
+}
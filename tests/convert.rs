@@ -0,0 +1,84 @@
+extern crate cse262_project;
+
+use cse262_project::{Interpreter, FromValue, IntoValue, Value, RuntimeError};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+mod common;
+use common::parse;
+
+#[test]
+fn into_values() {
+  assert_eq!(Value::from(5), Value::Number(5));
+  assert_eq!(Value::from(7u8), Value::Number(7));
+  assert_eq!(Value::from(true), Value::Bool(true));
+  assert_eq!(Value::from("hi"), Value::String("hi".to_string()));
+  assert_eq!(Value::from("hi".to_string()), Value::String("hi".to_string()));
+  assert_eq!(Value::from(None::<i32>), Value::Nil);
+  assert_eq!(Value::from(Some(1)), Value::Number(1));
+  assert_eq!(Value::from(vec![1, 2]), Value::List(vec![Value::Number(1), Value::Number(2)]));
+  let mut map = HashMap::new();
+  map.insert("a".to_string(), vec![true]);
+  let mut expected = HashMap::new();
+  expected.insert("a".to_string(), Value::List(vec![Value::Bool(true)]));
+  assert_eq!(map.into_value(), Ok(Value::Map(expected)));
+}
+
+#[test]
+fn wide_integers_are_checked() {
+  assert_eq!(Value::try_from(5u64), Ok(Value::Number(5)));
+  assert_eq!(Value::try_from(-5i64), Ok(Value::Number(-5)));
+  assert_eq!(Value::try_from(i32::MAX as usize), Ok(Value::Number(i32::MAX)));
+  assert_eq!(Value::try_from(i32::MAX as u32 + 1), Err("Number out of range"));
+  assert_eq!(Value::try_from(i64::MIN), Err("Number out of range"));
+  assert_eq!(Value::try_from(isize::MAX), Err("Number out of range"));
+  assert_eq!(Value::try_from(u64::MAX), Err("Number out of range"));
+  assert_eq!(vec![1usize, 2].into_value(), Ok(Value::List(vec![Value::Number(1), Value::Number(2)])));
+  assert_eq!(vec![Some(1u64), Some(u64::MAX)].into_value(), Err("Number out of range"));
+  assert_eq!(None::<usize>.into_value(), Ok(Value::Nil));
+}
+
+#[test]
+fn from_values() {
+  assert_eq!(i32::try_from(Value::Number(5)), Ok(5));
+  assert_eq!(i64::from_value(Value::Number(-5)), Ok(-5));
+  assert_eq!(u8::try_from(Value::Number(300)), Err("Number out of range"));
+  assert_eq!(u32::try_from(Value::Number(-1)), Err("Number out of range"));
+  assert_eq!(i32::try_from(Value::Bool(true)), Err("Expected a Number"));
+  assert_eq!(bool::try_from(Value::Bool(false)), Ok(false));
+  assert_eq!(String::try_from(Value::Number(1)), Err("Expected a String"));
+  assert_eq!(Option::<i32>::from_value(Value::Nil), Ok(None));
+  assert_eq!(Option::<i32>::from_value(Value::Number(3)), Ok(Some(3)));
+  assert_eq!(Vec::<i32>::try_from(Value::List(vec![Value::Number(1), Value::Number(2)])), Ok(vec![1, 2]));
+  assert_eq!(Vec::<i32>::try_from(Value::List(vec![Value::Number(1), Value::Nil])), Err("Expected a Number"));
+  assert_eq!(Vec::<i32>::try_from(Value::Nil), Err("Expected a List"));
+  let map: HashMap<String, String> = HashMap::try_from(Value::from(vec![("k".to_string(), "v")].into_iter().collect::<HashMap<_, _>>())).unwrap();
+  assert_eq!(map["k"], "v");
+  assert_eq!(HashMap::<String, i32>::try_from(Value::Nil), Err("Expected a Map"));
+}
+
+fn repeat(text: String, times: i32) -> Result<String, RuntimeError> {
+  Ok(text.repeat(times.max(0) as usize))
+}
+
+#[test]
+fn typed_host_functions() {
+  let mut interpreter = Interpreter::new()
+    .typed_function("repeat", repeat)
+    .typed_function("add", |a: i32, b: i32| a.checked_add(b).ok_or("Integer overflow"))
+    .typed_function("answer", || Ok(42))
+    .typed_function("sum", |list: Vec<i32>| Ok(list.iter().sum::<i32>()))
+    .typed_function("maybe", |n: Option<i32>| Ok(n.map(|n| n + 1)))
+    .typed_function("length", |text: String| Ok(text.len()))
+    .typed_function("huge", || Ok(u64::MAX));
+  assert_eq!(interpreter.run(&parse(r#"repeat("ab",3)"#)), Ok(Value::String("ababab".to_string())));
+  assert_eq!(interpreter.run(&parse(r#"add(answer(),1)"#)), Ok(Value::Number(43)));
+  assert_eq!(interpreter.run(&parse(r#"add(true,1)"#)), Err("Expected a Number"));
+  assert_eq!(interpreter.run(&parse(r#"add(1)"#)), Err("Wrong number of arguments"));
+  assert_eq!(interpreter.run(&parse(r#"maybe(nil)"#)), Ok(Value::Nil));
+  assert_eq!(interpreter.run(&parse(r#"maybe(1)"#)), Ok(Value::Number(2)));
+  assert_eq!(interpreter.run(&parse(r#"length("abc") * 2"#)), Ok(Value::Number(6)));
+  assert_eq!(interpreter.run(&parse(r#"huge()"#)), Err("Number out of range"));
+  interpreter.set_global("numbers", vec![1, 2, 3].into());
+  assert_eq!(interpreter.run(&parse(r#"sum(numbers)"#)), Ok(Value::Number(6)));
+}
@@ -0,0 +1,73 @@
+extern crate cse262_project;
+
+use cse262_project::{outline, to_json, Node};
+
+mod common;
+use common::parse;
+
+#[test]
+fn outline_indents_children() {
+  assert_eq!(outline(&parse(r#"let x = add(1,true);"#)), "Program
+  Statement
+    VariableDefine
+      Identifier \"x\"
+      Expression
+        FunctionCall \"add\"
+          FunctionArguments
+            Expression
+              Number 1
+            Expression
+              Bool true
+");
+}
+
+#[test]
+fn outline_labels_lists() {
+  assert_eq!(outline(&parse(r#"fn f(){while x { return; }}"#)), "Program
+  FunctionDefine
+    Identifier \"f\"
+    While
+      condition:
+        Condition
+          Identifier \"x\"
+      children:
+        Statement
+          FunctionReturn
+");
+  assert_eq!(outline(&Node::Local{value: "x".to_string(), slot: 2}), "Local \"x\" slot=2\n");
+}
+
+#[test]
+fn json_has_one_object_per_node() {
+  assert_eq!(to_json(&parse(r#"1+nil"#)), r#"{
+  "type": "Program",
+  "children": [
+    {
+      "type": "Expression",
+      "children": [
+        {
+          "type": "MathExpression",
+          "name": "+",
+          "children": [
+            {
+              "type": "Number",
+              "value": 1
+            },
+            {
+              "type": "Nil"
+            }
+          ]
+        }
+      ]
+    }
+  ]
+}
+"#);
+  assert_eq!(to_json(&Node::FunctionReturn{children: vec![]}), "{\n  \"type\": \"FunctionReturn\",\n  \"children\": []\n}\n");
+}
+
+#[test]
+fn json_escapes_strings() {
+  let node = Node::String{value: "say \"hi\"\\\n\u{1}é".to_string()};
+  assert_eq!(to_json(&node), "{\n  \"type\": \"String\",\n  \"value\": \"say \\\"hi\\\"\\\\\\n\\u0001é\"\n}\n");
+}
@@ -0,0 +1,164 @@
+extern crate cse262_project;
+
+use cse262_project::{program_with_syntax, format, format_syntax};
+
+mod common;
+use common::parse;
+
+// Formatting must give source that parses back to the same tree, and formatting that
+// source again must change nothing
+fn round_trip(source: &str) -> String {
+  let ast = parse(source);
+  let formatted = format(&ast);
+  assert_eq!(parse(&formatted), ast, "formatted as\n{}", formatted);
+  assert_eq!(format(&parse(&formatted)), formatted);
+  formatted
+}
+
+#[test]
+fn canonical_layout() {
+  assert_eq!(round_trip(r#"fn foo(a,b:int) -> int {
+let x=(a+1)*2;
+if !(x<3)&&b==2||false{
+return foo(x,b);
+}else if x>1{let z: string = "hi";}else {
+while x<1{let x=x+1;}
+}
+return;}
+let r = foo(1,2);
+println(r);
+r"#), r#"fn foo(a, b: int) -> int {
+  let x = (a + 1) * 2;
+  if !(x < 3) && b == 2 || false {
+    return foo(x, b);
+  } else if x > 1 {
+    let z: string = "hi";
+  } else {
+    while x < 1 {
+      let x = x + 1;
+    }
+  }
+  return;
+}
+
+let r = foo(1, 2);
+println(r);
+r
+"#);
+}
+
+#[test]
+fn minimal_parentheses() {
+  let cases = [
+    ("(1+2)*3", "(1 + 2) * 3"),
+    ("1+(2*3)", "1 + 2 * 3"),
+    ("(1-2)-3", "1 - 2 - 3"),
+    ("1-(2-3)", "1 - (2 - 3)"),
+    // * and / group to the right
+    ("8/(4/2)", "8 / 4 / 2"),
+    ("(8/4)/2", "(8 / 4) / 2"),
+    ("(2^3)^2", "2 ^ 3 ^ 2"),
+    ("2^(3^2)", "2 ^ (3 ^ 2)"),
+    ("(2*3)^2", "(2 * 3) ^ 2"),
+    ("f((1+2),(x))", "f(1 + 2, x)"),
+  ];
+  for (source, formatted) in cases.iter() {
+    assert_eq!(round_trip(source), format!("{}\n", formatted), "formatting {}", source);
+  }
+}
+
+#[test]
+fn programs_round_trip() {
+  let sources = [
+    r#"fn main() -> int {
+  let s: string = "hello world";
+  if s == "hello world" && !false {
+    return add(1,2);
+  }
+  return missing();
+}
+fn add(a: int, b: int) -> int {
+  return a + b;
+}"#,
+    r#"if  !(true) && false  {
+  if (true) {
+    let x = 1;
+  } else if (false || true) && (1+1)*2==2 {
+    let y = 2;
+  } else {
+    let y = 4;
+  }
+} else if (1+1)==2 {
+  let x = 3;
+} else {
+  if 1+1==3 {
+    let y = 1;
+  }
+  let y = 1;
+}
+"#,
+    r#"fn main() {
+  let i = 0;
+  while i < 5 | !!false & true != false {
+    let i = i + 1;
+    print(i);
+  }
+  return i;
+}"#,
+    r#"fn empty(){} let x = if true { 1 } else { 2 }; let y = 0b101 + 0o17;"#,
+  ];
+  for source in sources.iter() {
+    round_trip(source);
+  }
+}
+
+fn format_keeping_comments(source: &str) -> String {
+  let (input, (ast, tree)) = program_with_syntax(source).unwrap();
+  assert_eq!(input, "");
+  format_syntax(&ast, &tree)
+}
+
+#[test]
+fn keeps_comments_and_blank_lines() {
+  let source = r#"// Adds things up
+fn add(a,b) {   // two numbers
+  // the sum
+  let s=a+b;
+
+  if s>10 {
+    // big
+    return 10; // capped
+  } else {
+    return s;
+    // unreachable
+  }
+}
+// the end
+let x=add(1,2); // three
+
+x
+"#;
+  let formatted = r#"// Adds things up
+fn add(a, b) { // two numbers
+  // the sum
+  let s = a + b;
+
+  if s > 10 {
+    // big
+    return 10; // capped
+  } else {
+    return s;
+    // unreachable
+  }
+}
+
+// the end
+let x = add(1, 2); // three
+
+x
+"#;
+  assert_eq!(format_keeping_comments(source), formatted);
+  assert_eq!(format_keeping_comments(formatted), formatted);
+  // With nothing to keep it is the same as format
+  assert_eq!(format_keeping_comments("let x=1;fn f(){}"), format(&parse("let x=1;fn f(){}")));
+}
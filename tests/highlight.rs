@@ -0,0 +1,57 @@
+extern crate cse262_project;
+
+use cse262_project::{program, semantic_tokens, Base, TokenClass};
+
+// Each token's class and text, leaving out whitespace
+fn classes(source: &str) -> Vec<(TokenClass, &str)> {
+  let tokens = semantic_tokens(source);
+  let mut end = 0;
+  for token in tokens.iter() {
+    assert_eq!(token.span.start, end);
+    end = token.span.end;
+  }
+  assert_eq!(end, source.len());
+  tokens.into_iter().filter(|t| t.class != TokenClass::Whitespace).map(|t| (t.class, &source[t.span])).collect()
+}
+
+#[test]
+fn classifies_every_span() {
+  use TokenClass::*;
+  let source = "fn add(a: int, b) -> int { // sum\n  return a+b;\n}\nlet x = add(0b101,0o17) * 10 == 0;\nprintln(\"hi there\");\nwhile !true && x_1 {}";
+  assert_eq!(classes(source), vec![
+    (Keyword, "fn"), (FunctionName, "add"), (Punctuation, "("), (Identifier, "a"), (Punctuation, ":"), (Keyword, "int"),
+    (Punctuation, ","), (Identifier, "b"), (Punctuation, ")"), (Punctuation, "->"), (Keyword, "int"), (Punctuation, "{"),
+    (Comment, "// sum"),
+    (Keyword, "return"), (Identifier, "a"), (Operator, "+"), (Identifier, "b"), (Punctuation, ";"),
+    (Punctuation, "}"),
+    (Keyword, "let"), (Identifier, "x"), (Operator, "="), (FunctionName, "add"), (Punctuation, "("),
+    (Number(Base::Binary), "0b101"), (Punctuation, ","), (Number(Base::Octal), "0o17"), (Punctuation, ")"),
+    (Operator, "*"), (Number(Base::Decimal), "10"), (Operator, "=="), (Number(Base::Decimal), "0"), (Punctuation, ";"),
+    (FunctionName, "println"), (Punctuation, "("), (String, "\"hi there\""), (Punctuation, ")"), (Punctuation, ";"),
+    (Keyword, "while"), (Operator, "!"), (Keyword, "true"), (Operator, "&&"), (Identifier, "x_1"), (Punctuation, "{"), (Punctuation, "}"),
+  ]);
+}
+
+// Half-typed and broken text still gets a class for every character
+#[test]
+fn broken_source() {
+  use TokenClass::*;
+  assert_eq!(classes("let s = \"a-b\" @ 0b 12ab \"open"), vec![
+    (Keyword, "let"), (Identifier, "s"), (Operator, "="), (Invalid, "\"a-b\""), (Invalid, "@"),
+    (Invalid, "0b"), (Invalid, "12ab"), (Invalid, "\"open"),
+  ]);
+  // Type names are only keywords in an annotation
+  assert_eq!(classes("let int = 1;")[1], (Identifier, "int"));
+  assert_eq!(classes("f (1)")[0], (FunctionName, "f"));
+  assert_eq!(TokenClass::Number(Base::Octal).scope(), "constant.numeric.octal");
+}
+
+// A number is a whole word, so a prefix with no digits, or a digit its base doesn't have,
+// is left over rather than read as a zero and a name
+#[test]
+fn malformed_numbers() {
+  assert_eq!(program("1; let x = 0b;").unwrap().0, "let x = 0b;");
+  assert_eq!(program("1; 0o8;").unwrap().0, "0o8;");
+  assert_eq!(program("1; 99999999999;").unwrap().0, "99999999999;");
+  assert_eq!(program("let x = 0o17;").unwrap().0, "");
+}
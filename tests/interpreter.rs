@@ -0,0 +1,128 @@
+extern crate cse262_project;
+
+use cse262_project::{Interpreter, Output, OutputBuffer, Value, RuntimeError};
+use std::cell::Cell;
+use std::rc::Rc;
+
+mod common;
+use common::parse;
+
+fn double(args: &[Value]) -> Result<Value, RuntimeError> {
+  match args[0] {
+    Value::Number(n) => Ok(Value::Number(n * 2)),
+    _ => Err("double expects a number"),
+  }
+}
+
+#[test]
+fn host_functions() {
+  let mut interpreter = Interpreter::new().function("double", 1, double);
+  assert_eq!(interpreter.run(&parse(r#"fn main(){let x = double(20) + 2; return double(x);}"#)), Ok(Value::Number(84)));
+}
+
+#[test]
+fn host_errors() {
+  let mut interpreter = Interpreter::new().function("double", 1, double);
+  assert_eq!(interpreter.run(&parse(r#"fn main(){return double("a");}"#)), Err("double expects a number"));
+  assert_eq!(interpreter.run(&parse(r#"fn main(){return double(1,2);}"#)), Err("Wrong number of arguments"));
+  assert_eq!(interpreter.run(&parse(r#"fn main(){return double();}"#)), Err("Wrong number of arguments"));
+}
+
+#[test]
+fn script_functions_come_first() {
+  let mut interpreter = Interpreter::new().function("double", 1, double);
+  assert_eq!(interpreter.run(&parse(r#"fn main(){return double(2);} fn double(n){return n + n + n;}"#)), Ok(Value::Number(6)));
+}
+
+#[test]
+fn closures() {
+  let calls = Rc::new(Cell::new(0));
+  let counter = calls.clone();
+  let mut interpreter = Interpreter::new().function("tick", 0, move |_| {
+    counter.set(counter.get() + 1);
+    Ok(Value::Number(counter.get()))
+  });
+  assert_eq!(interpreter.run(&parse(r#"fn main(){let a = tick(); let b = tick(); return tick();}"#)), Ok(Value::Number(3)));
+  assert_eq!(calls.get(), 3);
+}
+
+#[test]
+fn call_script_functions() {
+  let mut interpreter = Interpreter::new().function("double", 1, double);
+  interpreter.load(&parse(r#"fn add(a: int, b){return double(a) + b;} fn nothing(){}"#)).unwrap();
+  assert_eq!(interpreter.call("add", &[Value::Number(2), Value::Number(3)]), Ok(Value::Number(7)));
+  assert_eq!(interpreter.call("add", &[Value::Bool(true), Value::Number(3)]), Err("Argument does not match its type"));
+  assert_eq!(interpreter.call("add", &[Value::Number(2)]), Err("Wrong number of arguments"));
+  assert_eq!(interpreter.call("add", &[Value::Number(2), Value::Number(3), Value::Number(4)]), Err("Wrong number of arguments"));
+  assert_eq!(interpreter.call("nothing", &[Value::Nil]), Err("Wrong number of arguments"));
+  assert_eq!(interpreter.call("nothing", &[]), Ok(Value::Nil));
+  assert_eq!(interpreter.call("double", &[Value::Number(4)]), Ok(Value::Number(8)));
+  assert_eq!(interpreter.call("missing", &[]), Err("Undefined function"));
+}
+
+#[test]
+fn globals() {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_global("width", Value::Number(3));
+  interpreter.set_global("height", Value::Number(4));
+  assert_eq!(interpreter.run(&parse(r#"let area = width * height; area + 1"#)), Ok(Value::Number(13)));
+  assert_eq!(interpreter.global("area"), Some(&Value::Number(12)));
+  assert_eq!(interpreter.global("missing"), None);
+  assert_eq!(interpreter.globals().len(), 3);
+}
+
+#[test]
+fn functions_read_globals() {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_global("scale", Value::Number(10));
+  interpreter.load(&parse(r#"fn scaled(n){return n * scale;} fn shadowed(){let scale = 2; return scale;}"#)).unwrap();
+  assert_eq!(interpreter.call("scaled", &[Value::Number(5)]), Ok(Value::Number(50)));
+  // A let inside a function binds a local, leaving the global alone
+  assert_eq!(interpreter.call("shadowed", &[]), Ok(Value::Number(2)));
+  assert_eq!(interpreter.global("scale"), Some(&Value::Number(10)));
+}
+
+#[test]
+fn snippets_share_state() {
+  let mut interpreter = Interpreter::new();
+  assert_eq!(interpreter.run(&parse(r#"let count = 1;"#)), Ok(Value::Number(1)));
+  interpreter.load(&parse(r#"fn next(){return count + 1;}"#)).unwrap();
+  assert_eq!(interpreter.run(&parse(r#"let count = next();"#)), Ok(Value::Number(2)));
+  assert_eq!(interpreter.run(&parse(r#"let count = next(); count * 10"#)), Ok(Value::Number(30)));
+  assert_eq!(interpreter.global("count"), Some(&Value::Number(3)));
+}
+
+#[test]
+fn runs_only_the_latest_snippet() {
+  let buffer = OutputBuffer::new();
+  let mut interpreter = Interpreter::new().output(Output::new(buffer.clone()));
+  assert_eq!(interpreter.run(&parse(r#"let x = 1; println(x);"#)), Ok(Value::Nil));
+  assert_eq!(interpreter.run(&parse(r#"fn f(){return 2;}"#)), Err("Undefined function"));
+  assert_eq!(interpreter.run(&parse(r#"println(f() + x);"#)), Ok(Value::Nil));
+  assert_eq!(buffer.contents(), "1\n3\n");
+}
+
+#[test]
+fn builtins_on_collections() {
+  let mut interpreter = Interpreter::new();
+  let mut map = std::collections::HashMap::new();
+  map.insert("b".to_string(), Value::Nil);
+  map.insert("a".to_string(), Value::List(vec![Value::Number(1), Value::String("x".to_string())]));
+  interpreter.set_global("m", Value::Map(map));
+  assert_eq!(interpreter.run(&parse(r#"to_string(m)"#)), Ok(Value::String("{a: [1, x], b: nil}".to_string())));
+  assert_eq!(interpreter.run(&parse(r#"len(m)"#)), Ok(Value::Number(2)));
+  assert_eq!(interpreter.run(&parse(r#"type_of(m)"#)), Ok(Value::String("map".to_string())));
+  assert_eq!(interpreter.run(&parse(r#"get(get(m, "a"), 1)"#)), Ok(Value::String("x".to_string())));
+  assert_eq!(interpreter.run(&parse(r#"get(get(m, "a"), 2)"#)), Ok(Value::Nil));
+  assert_eq!(interpreter.run(&parse(r#"get(get(m, "a"), 0 - 1)"#)), Ok(Value::Nil));
+  assert_eq!(interpreter.run(&parse(r#"get(m, "c")"#)), Ok(Value::Nil));
+  assert_eq!(interpreter.run(&parse(r#"get(m, 1)"#)), Err("Expected a String"));
+  assert_eq!(interpreter.run(&parse(r#"get(get(m, "a"), "b")"#)), Err("Expected a Number"));
+  assert_eq!(interpreter.run(&parse(r#"get("ab", 0)"#)), Err("Expected a List or Map"));
+}
+
+#[test]
+fn host_functions_replace_builtins() {
+  let mut interpreter = Interpreter::new().function("abs", 1, |_| Ok(Value::Number(0)));
+  assert_eq!(interpreter.run(&parse(r#"abs(5)"#)), Ok(Value::Number(0)));
+}
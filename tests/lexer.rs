@@ -0,0 +1,38 @@
+extern crate cse262_project;
+
+use cse262_project::{parse_error, program, tokenize, TokenKind};
+
+fn kinds(source: &str) -> Vec<(TokenKind, String)> {
+  tokenize(source).into_iter().filter(|t| t.kind != TokenKind::Whitespace).map(|t| (t.kind, t.text)).collect()
+}
+
+// Keywords are whole words, names can't start with a digit, and every character ends up
+// in a token
+#[test]
+fn words() {
+  let expected = {
+    use TokenKind::*;
+    vec![
+      (Keyword, "let"), (Identifier, "letx"), (Operator, "="), (Keyword, "true"), (Operator, "=="), (Identifier, "true1"),
+      (Punctuation, ";"), (Identifier, "_x_1"), (Invalid, "1x"), (Number, "0b10"), (Number, "0o7"), (Invalid, "007"),
+      (Keyword, "fn"), (Identifier, "int"), (Comment, "// c"),
+    ]
+  };
+  let source = "let letx = true == true1; _x_1 1x 0b10 0o7 007 fn int // c";
+  assert_eq!(kinds(source), expected.into_iter().map(|(kind, text)| (kind, text.to_string())).collect::<Vec<_>>());
+  let tokens = tokenize(source);
+  assert_eq!(tokens.iter().map(|t| t.text.as_str()).collect::<String>(), source);
+  assert!(tokens.iter().all(|t| source[t.offset..t.offset + t.text.len()] == t.text));
+}
+
+// Reserved words can't name anything, and using one as a name is reported at the keyword
+#[test]
+fn reserved_words() {
+  let error = |source: &str| parse_error(source, &program(source)).map(|e| (e.span, e.message));
+  assert_eq!(error("let x = 1; let fn = 2;"), Some((15..17, "`fn` is a keyword and can't be used as a name".to_string())));
+  assert_eq!(error("fn f(a, while) {}"), Some((8..13, "`while` is a keyword and can't be used as a name".to_string())));
+  assert_eq!(error("let x = 1;\nfn true() {}\n"), Some((14..18, "`true` is a keyword and can't be used as a name".to_string())));
+  assert_eq!(error("let x = 1; @ 2\n3"), Some((11..14, "unexpected `@ 2`".to_string())));
+  assert_eq!(error(""), Some((0..0, "unexpected end of file".to_string())));
+  assert_eq!(error("let my_var = 1; my_var"), None);
+}
@@ -0,0 +1,82 @@
+extern crate cse262_project;
+
+use cse262_project::{run, run_with_limits, compile, execute_with_limits, Value, RunLimits, DEFAULT_MAX_DEPTH};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::parse;
+
+// Runs on both backends, which have to agree
+fn limited(source: &str, limits: RunLimits) -> Result<Value, &'static str> {
+  let p = parse(source);
+  let result = run_with_limits(&p, &limits);
+  assert_eq!(execute_with_limits(&compile(&p), &limits), result);
+  result
+}
+
+const FOREVER: &str = r#"fn main(){let i = 0; while true {let i = i + 1;}}"#;
+
+#[test]
+fn step_limit() {
+  let limits = RunLimits{max_steps: Some(1000), ..RunLimits::default()};
+  assert_eq!(limited(FOREVER, limits), Err("Step limit exceeded"));
+  assert_eq!(limited(r#"fn main(){return 1 + 2;}"#, limits), Ok(Value::Number(3)));
+}
+
+#[test]
+fn time_limit() {
+  let limits = RunLimits{timeout: Some(Duration::from_millis(50)), ..RunLimits::default()};
+  let start = Instant::now();
+  assert_eq!(limited(FOREVER, limits), Err("Time limit exceeded"));
+  assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn depth_limit() {
+  let limits = RunLimits{max_depth: 5, ..RunLimits::default()};
+  let source = r#"fn main(){return depth(10);} fn depth(n){if n == 0 {return 0;} return 1 + depth(n - 1);}"#;
+  assert_eq!(limited(source, limits), Err("Stack overflow"));
+  assert_eq!(limited(source, RunLimits::default()), Ok(Value::Number(10)));
+}
+
+#[test]
+fn default_depth_limit() {
+  let depth = |n| format!("fn main(){{return depth({});}} fn depth(n){{if n == 0 {{return 0;}} return 1 + depth(n - 1);}}", n);
+  // main takes a frame too
+  let deepest = DEFAULT_MAX_DEPTH as i32 - 1;
+  assert_eq!(limited(&depth(deepest), RunLimits::default()), Ok(Value::Number(deepest)));
+  assert_eq!(limited(&depth(deepest + 1), RunLimits::default()), Err("Stack overflow"));
+  // The virtual machine keeps its frames off the Rust stack, so it can go deeper
+  let limits = RunLimits{max_depth: 100_000, ..RunLimits::default()};
+  assert_eq!(execute_with_limits(&compile(&parse(&depth(20000))), &limits), Ok(Value::Number(20000)));
+  // The tree walker can too, on a bigger stack
+  let deep = thread::Builder::new().stack_size(256 << 20).spawn(move || {
+    run_with_limits(&parse(&depth(5000)), &RunLimits{max_depth: 10_000, ..RunLimits::default()})
+  });
+  assert_eq!(deep.unwrap().join().unwrap(), Ok(Value::Number(5000)));
+}
+
+#[test]
+fn size_limit() {
+  let limits = RunLimits{max_size: Some(5), ..RunLimits::default()};
+  assert_eq!(limited(r#"fn main(){return "too long";}"#, limits), Err("Size limit exceeded"));
+  assert_eq!(limited(r#"fn main(){return "short";}"#, limits), Ok(Value::String("short".to_string())));
+}
+
+#[test]
+fn first_limit_reached_wins() {
+  let limits = RunLimits{
+    max_steps: Some(100),
+    timeout: Some(Duration::from_secs(60)),
+    max_depth: 1000,
+    max_size: Some(1),
+  };
+  assert_eq!(limited(FOREVER, limits), Err("Step limit exceeded"));
+}
+
+#[test]
+fn huge_powers_finish() {
+  assert_eq!(run(&parse(r#"1 ^ 2000000000"#)), Ok(Value::Number(1)));
+  assert_eq!(run(&parse(r#"2 ^ 2000000000"#)), Err("Integer overflow"));
+}
@@ -0,0 +1,175 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// Sends each message to a fresh server, then the end of input, and gives back its exit
+// status and the bodies of everything it wrote
+fn session(messages: &[String]) -> (Output, Vec<String>) {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_cse262-lsp"))
+    .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+    .spawn().unwrap();
+  let mut stdin = child.stdin.take().unwrap();
+  for message in messages {
+    write!(stdin, "Content-Length: {}\r\n\r\n{}", message.len(), message).unwrap();
+  }
+  drop(stdin);
+  let output = child.wait_with_output().unwrap();
+  let mut rest = String::from_utf8(output.stdout.clone()).unwrap();
+  let mut bodies = vec![];
+  while let Some(header_end) = rest.find("\r\n\r\n") {
+    let length: usize = rest[..header_end].trim_start_matches("Content-Length: ").parse().unwrap();
+    let body_start = header_end + 4;
+    bodies.push(rest[body_start..body_start + length].to_string());
+    rest = rest[body_start + length..].to_string();
+  }
+  (output, bodies)
+}
+
+fn request(id: usize, method: &str, params: &str) -> String {
+  format!(r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#, id, method, params)
+}
+
+fn notify(method: &str, params: &str) -> String {
+  format!(r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#, method, params)
+}
+
+fn at(line: usize, character: usize) -> String {
+  format!(r#"{{"textDocument":{{"uri":"file:///a.src"}},"position":{{"line":{},"character":{}}}}}"#, line, character)
+}
+
+fn range(start: (usize, usize), end: (usize, usize)) -> String {
+  format!(r#"{{"start":{{"line":{},"character":{}}},"end":{{"line":{},"character":{}}}}}"#, start.0, start.1, end.0, end.1)
+}
+
+fn result(id: usize, result: &str) -> String {
+  format!(r#"{{"jsonrpc":"2.0","id":{},"result":{}}}"#, id, result)
+}
+
+const SOURCE: &str = r#"let limit = 10;
+fn add(a, b) {
+  let sum = a + b;
+  return sum;
+}
+fn main() {
+  let total = add(1,2);
+  return total + limit;
+}
+"#;
+
+fn open(text: &str) -> String {
+  notify("textDocument/didOpen", &format!(r#"{{"textDocument":{{"uri":"file:///a.src","languageId":"cse262","version":1,"text":{:?}}}}}"#, text))
+}
+
+fn diagnostics(diagnostics: &str) -> String {
+  format!(r#"{{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{{"uri":"file:///a.src","diagnostics":[{}]}}}}"#, diagnostics)
+}
+
+#[test]
+fn lifecycle() {
+  let (output, replies) = session(&[
+    request(1, "initialize", r#"{"capabilities":{}}"#),
+    notify("initialized", "{}"),
+    request(2, "textDocument/formatting", "{}"),
+    request(3, "shutdown", "null"),
+    notify("exit", "null"),
+  ]);
+  assert_eq!(replies, vec![
+    result(1, r#"{"capabilities":{"textDocumentSync":1,"definitionProvider":true,"hoverProvider":true,"documentSymbolProvider":true,"completionProvider":{}},"serverInfo":{"name":"cse262-lsp"}}"#),
+    r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32601,"message":"Method not found"}}"#.to_string(),
+    result(3, "null"),
+  ]);
+  assert_eq!(output.status.code(), Some(0));
+  // Exiting without being shut down first is an error
+  assert_eq!(session(&[notify("exit", "null")]).0.status.code(), Some(1));
+}
+
+#[test]
+fn diagnostics_on_change() {
+  let change = |text: &str| notify("textDocument/didChange", &format!(r#"{{"textDocument":{{"uri":"file:///a.src","version":2}},"contentChanges":[{{"text":{:?}}}]}}"#, text));
+  let (_, replies) = session(&[
+    open(SOURCE),
+    change("let x = y;\nlet z: int = true;\n@ 2\n"),
+    // What follows a broken line is still checked
+    change("let x = ;\nlet z = w;\n"),
+    notify("textDocument/didClose", r#"{"textDocument":{"uri":"file:///a.src"}}"#),
+    r#"{"jsonrpc":"2.0","id":1,"#.to_string(),
+  ]);
+  assert_eq!(replies, vec![
+    diagnostics(""),
+    diagnostics(&[
+      format!(r#"{{"range":{},"severity":1,"source":"cse262","message":"undefined variable `y`"}}"#, range((0, 8), (0, 9))),
+      format!(r#"{{"range":{},"severity":2,"source":"cse262","message":"annotation expects Number but found Bool"}}"#, range((1, 13), (1, 17))),
+      format!(r#"{{"range":{},"severity":1,"source":"cse262","message":"unexpected `@ 2`"}}"#, range((2, 0), (2, 3))),
+    ].join(",")),
+    diagnostics(&[
+      format!(r#"{{"range":{},"severity":1,"source":"cse262","message":"undefined variable `w`"}}"#, range((1, 8), (1, 9))),
+      format!(r#"{{"range":{},"severity":1,"source":"cse262","message":"unexpected `let x = ;`"}}"#, range((0, 0), (0, 9))),
+    ].join(",")),
+    diagnostics(""),
+    r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Expected a key"}}"#.to_string(),
+  ]);
+}
+
+#[test]
+fn navigation() {
+  let (_, replies) = session(&[
+    open(SOURCE),
+    // The call to add, a global read in main, and a parameter
+    request(1, "textDocument/definition", &at(6, 15)),
+    request(2, "textDocument/definition", &at(7, 19)),
+    request(3, "textDocument/definition", &at(2, 12)),
+    // A keyword isn't a name
+    request(4, "textDocument/definition", &at(7, 2)),
+    request(5, "textDocument/hover", &at(3, 10)),
+    request(6, "textDocument/hover", &at(6, 14)),
+    request(7, "textDocument/hover", &at(0, 5)),
+    request(8, "textDocument/documentSymbol", r#"{"textDocument":{"uri":"file:///a.src"}}"#),
+    request(9, "textDocument/hover", r#"{"textDocument":{"uri":"file:///b.src"},"position":{"line":0,"character":0}}"#),
+  ]);
+  let location = |start, end| format!(r#"{{"uri":"file:///a.src","range":{}}}"#, range(start, end));
+  let hover = |text: &str, start, end| format!(r#"{{"contents":{{"kind":"plaintext","value":"{}"}},"range":{}}}"#, text, range(start, end));
+  let add = format!(r#"{{"name":"add","detail":"fn add(a: Number, b: Number) -> Number","kind":12,"range":{},"selectionRange":{}}}"#, range((1, 0), (4, 1)), range((1, 3), (1, 6)));
+  let main = format!(r#"{{"name":"main","detail":"fn main() -> Number","kind":12,"range":{},"selectionRange":{}}}"#, range((5, 0), (8, 1)), range((5, 3), (5, 7)));
+  assert_eq!(&replies[1..], &[
+    result(1, &location((1, 3), (1, 6))),
+    result(2, &location((0, 4), (0, 9))),
+    result(3, &location((1, 7), (1, 8))),
+    result(4, "null"),
+    result(5, &hover("sum: Number", (3, 9), (3, 12))),
+    result(6, &hover("fn add(a: Number, b: Number) -> Number", (6, 14), (6, 17))),
+    result(7, &hover("limit: Number", (0, 4), (0, 9))),
+    result(8, &format!("[{},{}]", add, main)),
+    r#"{"jsonrpc":"2.0","id":9,"error":{"code":-32602,"message":"Unknown document"}}"#.to_string(),
+  ]);
+}
+
+#[test]
+fn nested_global() {
+  let (_, replies) = session(&[
+    open("if true {\n  let limit = 10;\n}\nfn get() {\n  return limit;\n}\n"),
+    request(1, "textDocument/definition", &at(4, 10)),
+  ]);
+  let location = format!(r#"{{"uri":"file:///a.src","range":{}}}"#, range((1, 6), (1, 11)));
+  assert_eq!(replies, vec![diagnostics(""), result(1, &location)]);
+}
+
+#[test]
+fn completion() {
+  let (_, replies) = session(&[
+    open(SOURCE),
+    // At the start of main's return, after total is defined
+    request(1, "textDocument/completion", &at(7, 2)),
+    // At the top level, before anything is defined
+    request(2, "textDocument/completion", &at(0, 0)),
+  ]);
+  let labels = |reply: &str| -> Vec<String> {
+    reply.split(r#""label":""#).skip(1).map(|rest| rest[..rest.find('"').unwrap()].to_string()).collect()
+  };
+  let inside = labels(&replies[1]);
+  assert_eq!(&inside[..4], &["total", "limit", "add", "main"]);
+  assert!(inside.contains(&"println".to_string()));
+  assert!(!inside.contains(&"sum".to_string()));
+  assert!(replies[1].contains(r#"{"label":"total","kind":6,"detail":"Number"}"#));
+  assert!(replies[1].contains(r#"{"label":"add","kind":3,"detail":"fn add(a: Number, b: Number) -> Number"}"#));
+  assert!(replies[1].contains(r#"{"label":"len","kind":3,"detail":"fn len(any) -> Number"}"#));
+  assert_eq!(&labels(&replies[2])[..2], &["add", "main"]);
+}
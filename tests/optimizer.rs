@@ -0,0 +1,149 @@
+extern crate cse262_project;
+
+use cse262_project::{run, compile, execute, optimize, fold_constants, eliminate_dead_branches, remove_unused_lets, inline_trivial_functions, Node, Value};
+
+mod common;
+use common::parse;
+
+// The optimized program gives the same result as the original on both backends
+fn same(source: &str) -> Node {
+  let p = parse(source);
+  let optimized = optimize(&p);
+  assert_eq!(run(&optimized), run(&p));
+  assert_eq!(execute(&compile(&optimized)), run(&p));
+  optimized
+}
+
+fn body(program: &Node, ix: usize) -> Vec<Node> {
+  match program {
+    Node::Program{children} => match &children[ix] {
+      Node::FunctionDefine{children} => children[1..].to_vec(),
+      _ => panic!("expected a function"),
+    },
+    _ => panic!("expected a program"),
+  }
+}
+
+fn returns(node: Node) -> Vec<Node> {
+  vec![Node::Statement{children: vec![Node::FunctionReturn{children: vec![Node::Expression{children: vec![node]}]}]}]
+}
+
+#[test]
+fn folds_math() {
+  let folded = fold_constants(&parse(r#"((10+2)*6)/4"#));
+  assert_eq!(folded, Node::Program{children: vec![Node::Expression{children: vec![Node::Number{value: 18}]}]});
+  same(r#"((10+2)*6)/4"#);
+}
+
+#[test]
+fn folds_huge_powers() {
+  let number = |value| Node::Program{children: vec![Node::Expression{children: vec![Node::Number{value}]}]};
+  assert_eq!(fold_constants(&parse(r#"1 ^ 2000000000"#)), number(1));
+  assert_eq!(fold_constants(&parse(r#"(0 - 1) ^ 2000000001"#)), number(-1));
+  same(r#"(0 - 1) ^ 2000000001"#);
+}
+
+#[test]
+fn folds_conditions() {
+  let p = same(r#"fn main(){if 1 < 2 {return 1;} return 2;}"#);
+  assert_eq!(body(&p, 0), returns(Node::Number{value: 1}));
+  let p = fold_constants(&parse(r#"fn main(){if !(1 == 2) && 3 >= 4 {return 1;} return 2;}"#));
+  match &body(&p, 0)[0] {
+    Node::If{condition, ..} => assert_eq!(condition[0], Node::Condition{conditions: vec![Node::Bool{value: false}]}),
+    other => panic!("expected an if, found {:?}", other),
+  }
+}
+
+#[test]
+fn keeps_failing_math() {
+  for source in [r#"1 / 0"#, r#"2147483647 + 1"#, r#"2 ^ 40"#, r#"true + 1"#, r#"fn main(){if 1 < "a" {return 1;}}"#] {
+    let p = parse(source);
+    assert_eq!(fold_constants(&p), p);
+  }
+  same(r#"fn main(){return 1 + true;}"#);
+}
+
+#[test]
+fn removes_dead_branches() {
+  let p = eliminate_dead_branches(&fold_constants(&parse(r#"fn main(){
+    if 1 > 2 {
+      return 1;
+    } else if true {
+      let x = 2;
+    } else {
+      return 3;
+    }
+    while false {
+      return 4;
+    }
+    return x;
+  }"#)));
+  assert_eq!(body(&p, 0), vec![
+    Node::Statement{children: vec![Node::VariableDefine{children: vec![
+      Node::Identifier{value: "x".to_string()},
+      Node::Expression{children: vec![Node::Number{value: 2}]},
+    ]}]},
+    returns(Node::Identifier{value: "x".to_string()}).remove(0),
+  ]);
+  same(r#"fn main(){if 1 > 2 {return 1;} else if true {let x = 2;} else {return 3;} return x;}"#);
+  same(r#"fn main(){let a = 1; if a > 2 {return 1;} else if false {return 2;} else {return 3;}}"#);
+}
+
+#[test]
+fn keeps_non_bool_conditions() {
+  let p = same(r#"fn main(){if 1 {return 1;} return 2;}"#);
+  assert!(matches!(body(&p, 0)[0], Node::If{..}));
+}
+
+#[test]
+fn removes_unused_lets() {
+  let p = remove_unused_lets(&parse(r#"fn main(){let x = 1; let y = 2; let z: int = "a"; let w = foo(); return y;} fn foo(){return 1;}"#));
+  let names: Vec<Node> = body(&p, 0).iter().filter_map(|n| match n {
+    Node::Statement{children} => match &children[0] {
+      Node::VariableDefine{children} => Some(children[0].clone()),
+      _ => None,
+    },
+    _ => None,
+  }).collect();
+  assert_eq!(names, vec![
+    Node::Identifier{value: "y".to_string()},
+    Node::Identifier{value: "z".to_string()},
+    Node::Identifier{value: "w".to_string()},
+  ]);
+  same(r#"fn main(){let x = 1; let y = 2; let z: int = "a"; return y;}"#);
+  same(r#"let x = 5;"#);
+}
+
+#[test]
+fn inlines_trivial_functions() {
+  let p = inline_trivial_functions(&parse(r#"fn main(){return double(4) + three();} fn double(a){return a * 2;} fn three(){return 3;}"#));
+  assert_eq!(body(&p, 0), returns(Node::MathExpression{name: "+".to_string(), children: vec![
+    Node::MathExpression{name: "*".to_string(), children: vec![Node::Number{value: 4}, Node::Number{value: 2}]},
+    Node::Number{value: 3},
+  ]}));
+  let p = same(r#"fn main(){return double(4) + three();} fn double(a){return a * 2;} fn three(){return 3;}"#);
+  assert_eq!(body(&p, 0), returns(Node::Number{value: 11}));
+}
+
+#[test]
+fn keeps_non_trivial_calls() {
+  for source in [
+    r#"fn main(){let x = 1; return double(x);} fn double(a){return a * 2;}"#,
+    r#"fn main(){return double(1);} fn double(a: int){return a * 2;}"#,
+    r#"fn main(){return double(1);} fn double(a){let b = a; return b * 2;}"#,
+    r#"fn main(){return double();} fn double(a){return a * 2;}"#,
+    r#"fn main(){return double(1, 2);} fn double(a){return a * 2;}"#,
+    r#"fn main(){return fact(1);} fn fact(a){return a * fact(a - 1);}"#,
+  ] {
+    let p = parse(source);
+    assert_eq!(inline_trivial_functions(&p), p);
+  }
+  assert_eq!(run(&optimize(&parse(r#"fn main(){return double();} fn double(a){return a * 2;}"#))), Err("Wrong number of arguments"));
+}
+
+#[test]
+fn later_definitions_win() {
+  let p = same(r#"fn main(){return one();} fn one(){return 1;} fn one(){return 2;}"#);
+  assert_eq!(body(&p, 0), returns(Node::Number{value: 2}));
+  assert_eq!(run(&p), Ok(Value::Number(2)));
+}
@@ -2,20 +2,33 @@ extern crate cse262_project;
 extern crate nom;
 
 #[allow(unused_imports)]
-use cse262_project::{program, Node, Value, run};
-use nom::IResult;
+use cse262_project::{program, outline, Node, Value, run, compile, execute, optimize};
+use cse262_project::{execute_with_output, Interpreter, Output, OutputBuffer, RunLimits, RuntimeError};
 
+// Runs a script with print writing to a buffer, giving its result and what it printed
+fn captured<F: FnOnce(Output) -> Result<Value, RuntimeError>>(f: F) -> (Result<Value, RuntimeError>, String) {
+  let buffer = OutputBuffer::new();
+  let result = f(Output::new(buffer.clone()));
+  (result, buffer.contents())
+}
 
+// Each case runs on the tree walker, the virtual machine and after optimizing, and all three
+// must return the expected value and print the expected output, which is nothing by default
 macro_rules! test {
-  ($func:ident, $test:tt, $expected:expr) => (
+  ($func:ident, $test:tt, $expected:expr) => (test!($func, $test, $expected, ""););
+  ($func:ident, $test:tt, $expected:expr, $output:expr) => (
     #[test]
     fn $func() -> Result<(),nom::Err<(&'static str, nom::error::ErrorKind)>> {
       match program($test) {
         Ok((input, p)) => {
           assert_eq!(input, "");
-          println!("p = {:?}", p);
-          println!("run(p) = {:?}", run(&p));
-          assert_eq!(run(&p), $expected);
+          println!("p =\n{}", outline(&p));
+          let expected = ($expected, $output.to_string());
+          let result = captured(|output| Interpreter::new().output(output).run(&p));
+          println!("run(p) = {:?}", result);
+          assert_eq!(result, expected);
+          assert_eq!(captured(|output| execute_with_output(&compile(&p), &RunLimits::default(), &output)), expected);
+          assert_eq!(captured(|output| Interpreter::new().output(output).run(&optimize(&p))), expected);
           Ok(())
         },
         Err(e) => Err(e),
@@ -45,6 +58,12 @@ test!(math_divide, r#"6 / 2"#, Ok(Value::Number(3)));
 test!(math_exponent, r#"2 ^ 4"#, Ok(Value::Number(16)));
 test!(math_more_terms, r#"10 + 2*6"#, Ok(Value::Number(22)));
 test!(math_more_terms_paren, r#"((10+2)*6)/4"#, Ok(Value::Number(18)));
+test!(math_divide_by_zero, r#"1 / 0"#, Err("Division by zero"));
+test!(math_overflow, r#"2147483647 + 1"#, Err("Integer overflow"));
+test!(math_exponent_overflow, r#"2 ^ 31"#, Err("Integer overflow"));
+test!(math_negative_exponent, r#"2 ^ (0 - 1)"#, Ok(Value::Number(1)));
+test!(math_on_bool, r#"true + 1"#, Err("Cannot do math on String or Bool"));
+test!(math_on_string, r#"let x = "a" * 2;"#, Err("Cannot do math on String or Bool"));
 test!(assign_math, r#"let x = 1 + 1;"#, Ok(Value::Number(2)));
 test!(assign_function, r#"let x = foo();"#, Err("Undefined function"));
 test!(assign_function_arguments, r#"let x = foo(a,b,c);"#, Err("Undefined function"));
@@ -70,6 +89,133 @@ fn bar(a) {
 fn main() {
   return foo(1,2,3);  
 }"#, Ok(Value::Number(6)));
+test!(nil, r#"nil"#, Ok(Value::Nil));
+test!(variable_nil, r#"let x = nil;"#, Ok(Value::Nil));
+test!(function_no_return, r#"fn main(){let x = 5;}"#, Ok(Value::Nil));
+test!(function_empty_body, r#"fn main(){}"#, Ok(Value::Nil));
+test!(function_call_no_return, r#"fn main(){return foo();} fn foo(){let x = 5;}"#, Ok(Value::Nil));
+test!(return_no_value, r#"fn main(){return;}"#, Ok(Value::Nil));
+test!(return_early, r#"fn main(){return 1; return 2;}"#, Ok(Value::Number(1)));
+test!(return_early_skips_rest, r#"fn main() {
+  let x = 1;
+  println("before");
+  return x;
+  println("after");
+}"#, Ok(Value::Number(1)), "before\n");
+test!(if_else_chain, r#"fn main() {
+  let x = 2;
+  if x == 1 {
+    return 10;
+  } else if x == 2 {
+    return 20;
+  } else {
+    return 30;
+  }
+}"#, Ok(Value::Number(20)));
+test!(if_else_fallthrough, r#"fn main() {
+  if 1 > 2 {
+    return 10;
+  } else {
+    let x = 30;
+  }
+  return x;
+}"#, Ok(Value::Number(30)));
+test!(if_without_return, r#"fn main(){ if true { let x = 1; } }"#, Ok(Value::Nil));
+test!(condition_operators, r#"fn main() {
+  if !(1 != 1) && (1 <= 2 || foo()) && 3 >= 3 {
+    return true;
+  }
+  return false;
+}"#, Ok(Value::Bool(true)));
+test!(while_loop, r#"fn main() {
+  let i = 0;
+  let total = 0;
+  while i < 5 {
+    let i = i + 1;
+    let total = total + i;
+  }
+  return total;
+}"#, Ok(Value::Number(15)));
+test!(return_inside_if, r#"fn main() {
+  if true {
+    println("in if");
+    return 1;
+  }
+  println("after");
+  return 2;
+}"#, Ok(Value::Number(1)), "in if\n");
+test!(return_inside_else, r#"fn main() {
+  if false {
+    let x = 1;
+  } else {
+    println("in else");
+    return 2;
+  }
+  println("after");
+}"#, Ok(Value::Number(2)), "in else\n");
+test!(return_inside_nested_if, r#"fn main() {
+  if true {
+    if 1 == 1 {
+      if false {
+        let x = 1;
+      } else {
+        println("innermost");
+        return 3;
+      }
+      println("after inner");
+    }
+    println("after outer");
+  }
+  println("after");
+  return 0;
+}"#, Ok(Value::Number(3)), "innermost\n");
+test!(return_inside_while, r#"fn main() {
+  while true {
+    println("in loop");
+    return 4;
+  }
+  println("after");
+}"#, Ok(Value::Number(4)), "in loop\n");
+test!(return_inside_while_if, r#"fn main() {
+  let i = 0;
+  while i < 10 {
+    let i = i + 1;
+    println("step");
+    if i == 3 {
+      return i;
+    }
+  }
+  println("after");
+  return 0;
+}"#, Ok(Value::Number(3)), "step\nstep\nstep\n");
+test!(return_unwinds_only_callee, r#"fn main() {
+  let x = bar();
+  return x + 1;
+}
+fn bar() {
+  while true {
+    if true {
+      return 1;
+    }
+  }
+  println("after");
+}"#, Ok(Value::Number(2)));
+test!(top_level_statements, r#"let x = 1; let y = x + 1; y * 10"#, Ok(Value::Number(20)));
+test!(top_level_globals, r#"let x = 5; fn get(){return x;} get() + 1"#, Ok(Value::Number(6)));
+test!(globals_shadowed_by_locals, r#"let x = 5; fn get(){let x = 1; return x;} get() + x"#, Ok(Value::Number(6)));
+test!(variable_annotated, r#"let x: int = 1;"#, Ok(Value::Number(1)));
+test!(variable_annotated_mismatch, r#"let x: string = 1;"#, Err("Variable does not match its type"));
+test!(function_annotated, r#"fn main() -> int {return add(1,2);} fn add(a: int, b: int) -> int {return a + b;}"#, Ok(Value::Number(3)));
+test!(function_annotated_argument_mismatch, r#"fn main(){return add(true,2);} fn add(a: int, b: int) -> int {return a + b;}"#, Err("Argument does not match its type"));
+test!(function_annotated_return_mismatch, r#"fn main() -> int {return "one";}"#, Err("Return value does not match its type"));
+test!(tail_recursion, r#"fn main(){return count(100000,0);} fn count(n,total){if n == 0 {return total;} return count(n - 1,total + 1);}"#, Ok(Value::Number(100000)));
+test!(mutual_tail_recursion, r#"fn main(){return even(10001);} fn even(n){if n == 0 {return true;} return odd(n - 1);} fn odd(n){if n == 0 {return false;} return even(n - 1);}"#, Ok(Value::Bool(false)));
+test!(deep_recursion, r#"fn main(){return depth(100000);} fn depth(n){if n == 0 {return 0;} return 1 + depth(n - 1);}"#, Err("Stack overflow"));
+test!(annotated_tail_recursion, r#"fn main(){return count(100000);} fn count(n) -> int {if n == 0 {return 0;} return count(n - 1);}"#, Ok(Value::Number(0)));
+test!(tail_call_checks_caller_annotation, r#"fn main(){return f();} fn f() -> int {return g();} fn g(){return true;}"#, Err("Return value does not match its type"));
+test!(tail_call_chain_checks_every_annotation, r#"fn main(){return f(3);} fn f(n) -> int {if n == 0 {return 1;} return g(n - 1);} fn g(n) -> bool {return f(n);}"#, Err("Return value does not match its type"));
+test!(tail_call_return_checked, r#"fn main() -> int {return name();} fn name(){return "x";}"#, Err("Return value does not match its type"));
+test!(function_annotated_nil, r#"fn main() -> bool {}"#, Ok(Value::Nil));
 test!(binary, r#"0b1111011"#, Ok(Value::Number(123)));
 test!(octal, r#"0o173"#, Ok(Value::Number(123)));
 test!(multiple_conditions_if_stmts, r#"
@@ -92,7 +238,7 @@ if  !(true) && false  {
   
 }
 "#, Err("Unknown Expression"));
-/*test!(define_full_program_with_comments, r#"fn foo(a,b,c) {
+test!(define_full_program_with_comments, r#"fn foo(a,b,c) {
   let x = a + 1;
   let y = bar(c - b);
   // x = 5;
@@ -109,6 +255,59 @@ fn main() {
   // please give me a good grade
   // I worked very hard
   return foo(1,2,3);  
-}"#, Ok(Value::Number(6)));*/
-
+}"#, Ok(Value::Number(6)));
 
+test!(builtin_math, r#"abs(2 - 7) + min(3,7) + max(3,7) + sqrt(17)"#, Ok(Value::Number(19)));
+test!(builtin_rounding_division, r#"floor(0 - 7,2) * 10 + ceil(0 - 7,2) + floor(7,2) * 100 + ceil(7,2) * 1000"#, Ok(Value::Number(4257)));
+test!(builtin_division_by_zero, r#"floor(1,0)"#, Err("Division by zero"));
+test!(builtin_abs_overflow, r#"abs(0 - 2147483647 - 1)"#, Err("Integer overflow"));
+test!(builtin_sqrt_max, r#"sqrt(2147483647) + sqrt(0) + sqrt(1) + sqrt(2)"#, Ok(Value::Number(46342)));
+test!(builtin_sqrt_negative, r#"sqrt(0 - 4)"#, Err("Cannot take the square root of a negative number"));
+test!(builtin_strings, r#"len(to_string(12345)) + parse_int("42")"#, Ok(Value::Number(47)));
+test!(builtin_parse_int_fails, r#"parse_int("forty two")"#, Ok(Value::Nil));
+test!(builtin_type_of, r#"type_of("x")"#, Ok(Value::String("string".to_string())));
+test!(builtin_assert, r#"assert(true)"#, Ok(Value::Nil));
+test!(builtin_assert_fails, r#"assert(false)"#, Err("Assertion failed"));
+test!(builtin_assert_not_bool, r#"assert(1)"#, Err("Condition is not a Bool"));
+test!(builtin_len_of_number, r#"len(1)"#, Err("Expected a String, List or Map"));
+test!(builtin_wrong_arity, r#"min(1)"#, Err("Wrong number of arguments"));
+test!(builtin_in_return_position, r#"fn dist(a, b) {
+  return abs(a - b);
+}
+dist(2,9)"#, Ok(Value::Number(7)));
+test!(builtin_replaced_by_script, r#"fn abs(x) {
+  return 7;
+}
+abs(1)"#, Ok(Value::Number(7)));
+test!(expression_statements, r#"fn main() {
+  print("counting ");
+  let i = 0;
+  while i < 3 {
+    let i = i + 1;
+    println(i);
+  }
+  return i;
+}"#, Ok(Value::Number(3)), "counting 1\n2\n3\n");
+test!(top_level_expression_statement, r#"println("done");"#, Ok(Value::Nil), "done\n");
+test!(print_stops_at_error, r#"println(1);
+println(2 / 0);
+println(3);"#, Err("Division by zero"), "1\n");
+test!(underscores_in_names, r#"fn add_one(my_value) {
+  return my_value + 1;
+}
+let _x = add_one(1);
+_x"#, Ok(Value::Number(2)));
+test!(comments, r#"// Doubles a number
+fn double(n) { // one argument
+  // times two
+  return n * 2; // the result
+}
+// call it
+double(21) // done
+"#, Ok(Value::Number(42)));
+test!(keyword_prefixes, r#"let letx = 1;
+let true_value = 2;
+let iffy = 3;
+letx + true_value + iffy"#, Ok(Value::Number(6)));
+test!(spaces_between_tokens, r#"fn add ( a , b ) -> int { return a + b ; }
+add ( 1 , 2 )"#, Ok(Value::Number(3)));
@@ -0,0 +1,71 @@
+extern crate cse262_project;
+
+use cse262_project::{outline, parse, parse_with_syntax, program, run, Value};
+
+fn messages(source: &str) -> Vec<(usize, String)> {
+  parse(source).1.into_iter().map(|e| (e.span.start, e.message)).collect()
+}
+
+// Each broken item is reported, and everything else still parses and runs
+#[test]
+fn reports_every_error() {
+  let source = "let a = 1;\nlet = 2;\nfn f(x) {\n  let y = ;\n  return x;\n}\nlet b = a + 1 @;\nfn g(n) { return n * 2; }\ng(a)\n";
+  assert_eq!(messages(source), vec![
+    (11, "unexpected `let = 2;`".to_string()),
+    (20, "unexpected `fn f(x) {`".to_string()),
+    (56, "unexpected `let b = a + 1 @;`".to_string()),
+  ]);
+  let (ast, _) = parse(source);
+  assert_eq!(run(&ast), Ok(Value::Number(2)));
+}
+
+// A keyword used as a name is pointed at, and parsing goes on after its function
+#[test]
+fn keyword_names() {
+  let source = "fn f(a, if) {\n  return a;\n}\nlet fn = 1;\n3";
+  assert_eq!(messages(source), vec![
+    (8, "`if` is a keyword and can't be used as a name".to_string()),
+    (32, "`fn` is a keyword and can't be used as a name".to_string()),
+  ]);
+  assert_eq!(outline(&parse(source).0), outline(&program("3").unwrap().1));
+}
+
+// A program that parses gives the same tree either way, and nothing at all is an error
+#[test]
+fn clean_source() {
+  let source = "fn double(n) { return n * 2; }\nlet x = double(2);\nx";
+  let (ast, errors) = parse(source);
+  assert!(errors.is_empty());
+  assert_eq!(ast, program(source).unwrap().1);
+  assert_eq!(messages("// nothing\n"), vec![(11, "unexpected end of file".to_string())]);
+}
+
+// The syntax tree keeps the text that didn't parse, so it still gives back the source
+#[test]
+fn partial_tree_is_lossless() {
+  let source = "let a = 1; // one\n}} @ let = ;\nfn f() { return a; }\n";
+  let (ast, tree, errors) = parse_with_syntax(source);
+  assert_eq!(errors.len(), 3);
+  assert_eq!(tree.to_string(), source);
+  assert_eq!(tree.span(), 0..source.len());
+  assert_eq!(tree.nodes().count(), 2);
+  assert_eq!(ast, program("let a = 1; fn f() { return a; }").unwrap().1);
+}
+
+// The tree comes from the tokens the parser took, so any source gives itself back
+#[test]
+fn any_source_gives_a_tree() {
+  let sources = [
+    "",
+    "((((",
+    "let x = ((1 + 2);\nlet y = \"open",
+    "fn f(a: int -> int { return ((a)) }\nf(1) ^ (2) ^ ((3))",
+    "if !!(1 == 2) && ((true)) { 1 } else if { 2 } else",
+    "let é = 1; // ünïcode\n\t\r\n",
+  ];
+  for source in sources.iter() {
+    let (_, tree, _) = parse_with_syntax(source);
+    assert_eq!(tree.to_string(), *source);
+    assert_eq!(tree.span(), 0..source.len());
+  }
+}
@@ -0,0 +1,152 @@
+extern crate cse262_project;
+
+use cse262_project::{resolve, resolve_with_hosts, run, Interpreter, Node, Value, ResolveError};
+
+mod common;
+use common::parse;
+
+fn errors(source: &str) -> Vec<String> {
+  match resolve(&parse(source)) {
+    Ok(_) => vec![],
+    Err(errors) => errors.iter().map(ResolveError::to_string).collect(),
+  }
+}
+
+#[test]
+fn assigns_slots() {
+  let resolved = resolve(&parse(r#"fn main(){return add(1,2);} fn add(a,b){let c = a + b; let a = c; return a;}"#)).unwrap();
+  let local = |value: &str, slot| Node::Local{value: value.to_string(), slot};
+  let expression = |node| Node::Expression{children: vec![node]};
+  let statement = |node| Node::Statement{children: vec![node]};
+  match resolved {
+    Node::Program{children} => {
+      assert_eq!(children[1], Node::FunctionDefine{children: vec![
+        Node::Identifier{value: "add".to_string()},
+        Node::FunctionArguments{children: vec![expression(local("a", 0)), expression(local("b", 1))]},
+        statement(Node::VariableDefine{children: vec![
+          local("c", 2),
+          expression(Node::MathExpression{name: "+".to_string(), children: vec![local("a", 0), local("b", 1)]}),
+        ]}),
+        statement(Node::VariableDefine{children: vec![local("a", 0), expression(local("c", 2))]}),
+        statement(Node::FunctionReturn{children: vec![expression(local("a", 0))]}),
+      ]});
+    },
+    _ => panic!("expected a program"),
+  }
+}
+
+#[test]
+fn resolved_programs_run() {
+  let sources = [
+    r#"fn foo(a,b,c) {
+  let x = a + 1;
+  let y = bar(c - b);
+  return x * y;
+}
+
+fn bar(a) {
+  return a * 3;
+}
+
+fn main() {
+  return foo(1,2,3);
+}"#,
+    r#"fn main() {
+  let i = 0;
+  let total = 0;
+  while i < 5 {
+    let i = i + 1;
+    let total = total + i;
+  }
+  return total;
+}"#,
+    r#"let x = 1 + 1;"#,
+  ];
+  for source in sources.iter() {
+    let p = parse(source);
+    let resolved = resolve(&p).unwrap();
+    assert_eq!(run(&resolved), run(&p));
+  }
+  assert_eq!(run(&resolve(&parse(sources[0])).unwrap()), Ok(Value::Number(6)));
+}
+
+#[test]
+fn reports_every_unresolved_name() {
+  assert_eq!(errors(r#"fn main() {
+  let x = y + 1;
+  return foo(x,z);
+}"#), vec![
+    "in function `main`: undefined variable `y`",
+    "in function `main`: undefined function `foo`",
+    "in function `main`: undefined variable `z`",
+  ]);
+}
+
+#[test]
+fn variable_used_in_own_definition() {
+  assert_eq!(errors(r#"let x = x + 1;"#), vec!["in function `main`: undefined variable `x`"]);
+}
+
+#[test]
+fn variable_defined_in_earlier_block() {
+  assert_eq!(errors(r#"fn main() {
+  if true {
+    let x = 1;
+  }
+  return x;
+}"#), Vec::<String>::new());
+}
+
+#[test]
+fn duplicate_function() {
+  assert_eq!(errors(r#"fn main(){return 1;} fn main(){return 2;}"#),
+    vec!["in function `main`: function `main` is defined more than once"]);
+}
+
+#[test]
+fn duplicate_parameter() {
+  assert_eq!(errors(r#"fn main(){return add(1,2);} fn add(a,a){return a;}"#),
+    vec!["in function `add`: parameter `a` is declared more than once"]);
+}
+
+#[test]
+fn functions_are_visible_before_definition() {
+  assert_eq!(errors(r#"fn main(){return foo();} fn foo(){return 5;}"#), Vec::<String>::new());
+}
+
+#[test]
+fn globals_stay_unresolved() {
+  let source = r#"let x = 5; fn get(){return x;} get()"#;
+  assert_eq!(errors(source), Vec::<String>::new());
+  assert_eq!(run(&resolve(&parse(source)).unwrap()), Ok(Value::Number(5)));
+}
+
+#[test]
+fn nested_top_level_lets_are_globals() {
+  assert_eq!(errors(r#"if true { let x = 5; } fn get(){return x;}"#), Vec::<String>::new());
+}
+
+#[test]
+fn branches_resolve_in_order() {
+  let source = r#"fn main(a) {
+  if a { let x = 1; } else if x > 0 { return x; }
+  return 0;
+}"#;
+  assert_eq!(errors(source), Vec::<String>::new());
+}
+
+#[test]
+fn builtins_are_defined() {
+  assert_eq!(errors(r#"fn main(){return max(abs(1),2);}"#), Vec::<String>::new());
+  assert_eq!(errors(r#"fn abs(a){return a;} abs(1)"#), Vec::<String>::new());
+}
+
+#[test]
+fn host_functions_are_defined() {
+  let source = r#"fn main(){return host(1) + 1;}"#;
+  assert_eq!(errors(source), vec!["in function `main`: undefined function `host`"]);
+  assert!(resolve_with_hosts(&parse(source), &["host"]).is_ok());
+  let mut interpreter = Interpreter::new().function("host", 1, |args| Ok(args[0].clone()));
+  let resolved = interpreter.resolve(&parse(source)).unwrap();
+  assert_eq!(interpreter.run(&resolved), Ok(Value::Number(2)));
+}
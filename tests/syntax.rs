@@ -0,0 +1,92 @@
+extern crate cse262_project;
+
+use cse262_project::{program, program_with_syntax, Node, SyntaxKind, SyntaxNode, TokenKind};
+
+fn parse(source: &str) -> (Node, SyntaxNode) {
+  let (input, (ast, tree)) = program_with_syntax(source).unwrap();
+  assert_eq!(input, "");
+  assert_eq!(program(source).unwrap().1, ast);
+  (ast, tree)
+}
+
+// The tokens give back the source character for character, each one from where it says
+#[test]
+fn lossless() {
+  let sources = [
+    "let x = 1;",
+    "  fn foo(a , b: int)->int {\n\treturn ((a)+(1))*  2;\n}\n\n\nfoo(1,2)\n",
+    "// leading\nfn main() { // after the brace\n  // inside\n  let s: string = \"hi there\"; // trailing\n\n  return s;\n  // at the end\n}\n// last\n",
+    "if  !(true) && false  {\n  if (true) {\n    let x = 1;\n  } else if (false || true) && (1+1)*2==2 {\n    let y = 2;\n  } else {\n    let y = 4;\n  }\n} else if ((1+1))==2 {\n  let x = 3;\n} else {\n  let y = 1;\n}\n",
+    "fn main() {\n  let i = 0;\n  while i < 5 | !!false & true != false {\n    let i = i + 1;\n  }\n  return;\n}",
+    "fn empty(){} let x = if true { 1 } else { 2 }; let y = 0b101 + 0o17 - 0 + (((((1)+2)+3)+4)+5);",
+    "let n = nil;\nprintln(f(g(1),\"a b\"));\nx",
+  ];
+  for source in sources.iter() {
+    let (_, tree) = parse(source);
+    assert_eq!(tree.to_string(), *source);
+    for token in tree.tokens() {
+      assert_eq!(&source[token.offset..token.offset + token.text.len()], token.text);
+    }
+    assert_eq!(tree.span(), 0..source.len());
+  }
+}
+
+#[test]
+fn comments_are_trivia() {
+  let (ast, _) = parse("// one\nlet x = 1; // two\n// three\nx // four\n");
+  assert_eq!(ast, program("let x = 1;\nx").unwrap().1);
+}
+
+#[test]
+fn tokens_are_classified() {
+  let (_, tree) = parse("let x: int = f(0b11) + \"s\"; // c");
+  let tokens: Vec<(TokenKind, &str)> = tree.tokens().iter()
+    .filter(|token| token.kind != TokenKind::Whitespace)
+    .map(|token| (token.kind, token.text.as_ref()))
+    .collect();
+  assert_eq!(tokens, vec![
+    (TokenKind::Keyword, "let"),
+    (TokenKind::Identifier, "x"),
+    (TokenKind::Punctuation, ":"),
+    (TokenKind::Keyword, "int"),
+    (TokenKind::Operator, "="),
+    (TokenKind::Identifier, "f"),
+    (TokenKind::Punctuation, "("),
+    (TokenKind::Number, "0b11"),
+    (TokenKind::Punctuation, ")"),
+    (TokenKind::Operator, "+"),
+    (TokenKind::String, "\"s\""),
+    (TokenKind::Punctuation, ";"),
+    (TokenKind::Comment, "// c"),
+  ]);
+}
+
+// Trivia between nodes belongs to the node around them, and parentheses to the node
+// around what they group
+#[test]
+fn nodes_follow_the_tree() {
+  let (_, tree) = parse("fn f() {\n  // note\n  return (1 + 2) * 3;\n}\n");
+  let function = tree.nodes().next().unwrap();
+  assert_eq!(function.kind, SyntaxKind::FunctionDefine);
+  assert_eq!(function.span(), 0..42);
+  assert!(function.tokens().iter().any(|token| token.kind == TokenKind::Comment));
+  let statement = function.nodes().nth(1).unwrap();
+  assert_eq!(statement.kind, SyntaxKind::Statement);
+  assert_eq!(statement.to_string(), "return (1 + 2) * 3;");
+  let times = statement.nodes().next().unwrap().nodes().next().unwrap().nodes().next().unwrap();
+  assert_eq!(times.kind, SyntaxKind::MathExpression);
+  assert_eq!(times.nodes().map(|node| node.to_string()).collect::<Vec<_>>(), vec!["1 + 2", "3"]);
+  assert_eq!(times.to_string(), "(1 + 2) * 3");
+}
+
+// Renaming through the tokens keeps everything else as it was written
+#[test]
+fn rewrites_keep_layout() {
+  let (_, mut tree) = parse("let x = 1;   // the start\n\nlet y = x*2; // double it\n");
+  for token in tree.tokens_mut() {
+    if token.kind == TokenKind::Identifier && token.text == "x" {
+      token.text = "start".to_string();
+    }
+  }
+  assert_eq!(tree.to_string(), "let start = 1;   // the start\n\nlet y = start*2; // double it\n");
+}
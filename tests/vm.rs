@@ -0,0 +1,99 @@
+extern crate cse262_project;
+
+use cse262_project::{resolve, run, run_with_limits, compile, execute, execute_with_limits, Value, Instruction, RunLimits};
+
+mod common;
+use common::parse;
+
+#[test]
+fn compiles_math() {
+  let bytecode = compile(&parse(r#"1 + 2 * 1"#));
+  assert_eq!(bytecode.constants, vec![Value::Number(1), Value::Number(2)]);
+  assert_eq!(bytecode.functions[0].code, vec![
+    Instruction::Constant(0),
+    Instruction::Constant(1),
+    Instruction::Constant(0),
+    Instruction::Multiply,
+    Instruction::Add,
+    Instruction::Return,
+    Instruction::Nil,
+    Instruction::Return,
+  ]);
+}
+
+#[test]
+fn locals_use_slots() {
+  let bytecode = compile(&parse(r#"fn main(){return add(1,2);} fn add(a,b){let c = a + b; return c;}"#));
+  let add = &bytecode.functions[1];
+  assert_eq!(add.params, vec![0, 1]);
+  assert_eq!(add.slots, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+  assert!(add.code.contains(&Instruction::Store(2)));
+}
+
+#[test]
+fn recursion() {
+  let p = parse(r#"fn main() {
+  return fib(15);
+}
+fn fib(n) {
+  if n < 2 {
+    return n;
+  }
+  return fib(n - 1) + fib(n - 2);
+}"#);
+  assert_eq!(execute(&compile(&p)), Ok(Value::Number(610)));
+  assert_eq!(execute(&compile(&p)), run(&p));
+}
+
+#[test]
+fn resolved_tree() {
+  let p = parse(r#"fn main() {
+  let i = 0;
+  while i < 10 {
+    let i = i + 1;
+  }
+  return i;
+}"#);
+  assert_eq!(execute(&compile(&resolve(&p).unwrap())), Ok(Value::Number(10)));
+}
+
+#[test]
+fn wrong_number_of_arguments() {
+  for source in &[
+    r#"fn main(){return add(1);} fn add(a,b){return a + b;}"#,
+    // Extra arguments are an error too, before any of them run
+    r#"fn main(){return f(1, 2/0);} fn f(a){return a;}"#,
+    r#"fn main(){let x = f(1); return x;} fn f(){return 1;}"#,
+    r#"fn main(){return f(1, 2);} fn f(a){return a;}"#,
+  ] {
+    let p = parse(source);
+    assert_eq!(run(&p), Err("Wrong number of arguments"));
+    assert_eq!(execute(&compile(&p)), Err("Wrong number of arguments"));
+  }
+}
+
+#[test]
+fn no_main() {
+  let p = parse(r#"fn foo(){return 1;}"#);
+  assert_eq!(run(&p), Err("Undefined function"));
+  assert_eq!(execute(&compile(&p)), Err("Undefined function"));
+}
+
+#[test]
+fn tail_calls() {
+  let bytecode = compile(&parse(r#"fn main(){return count(3);} fn count(n){if n == 0 {return 0;} return count(n - 1);}"#));
+  assert_eq!(bytecode.functions[0].code, vec![Instruction::Constant(0), Instruction::TailCall(1), Instruction::Nil, Instruction::Return]);
+  assert!(bytecode.functions[1].code.contains(&Instruction::TailCall(1)));
+  assert!(!bytecode.functions[1].code.contains(&Instruction::Call(1)));
+}
+
+#[test]
+fn max_depth() {
+  let p = parse(r#"fn main(){return depth(10);} fn depth(n){if n == 0 {return 0;} return 1 + depth(n - 1);}"#);
+  // main tail calls depth, which then nests eleven calls deep
+  let depth = |max_depth| RunLimits{max_depth, ..RunLimits::default()};
+  assert_eq!(run_with_limits(&p, &depth(11)), Ok(Value::Number(10)));
+  assert_eq!(run_with_limits(&p, &depth(10)), Err("Stack overflow"));
+  assert_eq!(execute_with_limits(&compile(&p), &depth(11)), Ok(Value::Number(10)));
+  assert_eq!(execute_with_limits(&compile(&p), &depth(10)), Err("Stack overflow"));
+}